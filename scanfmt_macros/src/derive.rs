@@ -0,0 +1,205 @@
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{parse_quote, Attribute, Data, DeriveInput, Error, Fields, LitStr, Member};
+
+use crate::fmt::{Input, Mode, Target};
+
+pub(crate) fn derive_scan(input: DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+
+    // types with a lifetime borrow their values from the input for it.
+    let lifetimes = input.generics.lifetimes().collect::<Vec<_>>();
+    let (trait_, input_ty) = match lifetimes[..] {
+        [] => (quote!(::scanfmt::Scan), quote!(&str)),
+        [param] => {
+            let lifetime = &param.lifetime;
+            (
+                quote!(::scanfmt::ScanBorrowed<#lifetime>),
+                quote!(&#lifetime str),
+            )
+        }
+        _ => {
+            return Err(Error::new_spanned(
+                &input.generics,
+                "`Scan` cannot be derived for types with more than one lifetime",
+            ))
+        }
+    };
+
+    // like the derives of std, every type parameter must implement the trait.
+    let mut generics = input.generics.clone();
+    let params = generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    let predicates = &mut generics.make_where_clause().predicates;
+    for param in params {
+        predicates.push(parse_quote!(#param: #trait_));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (scan, start) = match &input.data {
+        Data::Struct(data) => {
+            let fmt = pattern(&input.attrs)?.ok_or_else(|| {
+                Error::new_spanned(
+                    name,
+                    "deriving `Scan` requires a format string, like `#[scan(\"{x}, {y}\")]`",
+                )
+            })?;
+            fields(&fmt, quote!(Self), &data.fields)?
+        }
+        Data::Enum(data) => {
+            if let Some(attr) = input.attrs.iter().find(|attr| attr.path.is_ident("scan")) {
+                return Err(Error::new_spanned(
+                    attr,
+                    "the variants of an enum have their own format strings",
+                ));
+            }
+            if data.variants.is_empty() {
+                return Err(Error::new_spanned(
+                    name,
+                    "`Scan` cannot be derived for enums without variants",
+                ));
+            }
+
+            let mut scans = Vec::with_capacity(data.variants.len());
+            let mut starts = Vec::with_capacity(data.variants.len());
+            for variant in &data.variants {
+                let ident = &variant.ident;
+                // unit variants match their name by default.
+                let fmt = match (pattern(&variant.attrs)?, &variant.fields) {
+                    (Some(fmt), _) => fmt,
+                    (None, Fields::Unit) => LitStr::new(&ident.to_string(), ident.span()),
+                    (None, _) => {
+                        return Err(Error::new_spanned(
+                            ident,
+                            format!(
+                            "variant `{}` requires a format string, like `#[scan(\"{}({{}})\")]`",
+                            ident, ident
+                        ),
+                        ))
+                    }
+                };
+                let (scan, start) = fields(&fmt, quote!(Self::#ident), &variant.fields)?;
+                scans.push(quote! {
+                    (|| -> ::core::result::Result<Self, ::scanfmt::macro_support::ScanError> {
+                        #scan
+                    })()
+                });
+                starts.push(start);
+            }
+
+            // variants are tried in order, and the error that got the furthest
+            // into the input is returned if none of them match.
+            let first = &scans[0];
+            let rest = &scans[1..];
+            let scan = quote! {
+                let __error = match #first {
+                    ::core::result::Result::Ok(__value) => return ::core::result::Result::Ok(__value),
+                    ::core::result::Result::Err(__e) => __e,
+                };
+                #(
+                    let __error = match #rest {
+                        ::core::result::Result::Ok(__value) => return ::core::result::Result::Ok(__value),
+                        ::core::result::Result::Err(__e) => {
+                            ::scanfmt::macro_support::furthest(__error, __e)
+                        }
+                    };
+                )*
+                ::core::result::Result::Err(__error)
+            };
+            (scan, quote!(#((#starts))||*))
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                name,
+                "`Scan` can only be derived for structs and enums",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics #trait_ for #name #ty_generics #where_clause {
+            fn is_valid_start(__c: char) -> bool {
+                #start
+            }
+
+            fn scan(__s: #input_ty) -> ::core::result::Result<Self, ::scanfmt::macro_support::ScanError> {
+                #scan
+            }
+        }
+    })
+}
+
+/// Finds the format string in the `#[scan("...")]` attribute, if any.
+fn pattern(attrs: &[Attribute]) -> syn::Result<Option<LitStr>> {
+    let mut attrs = attrs.iter().filter(|attr| attr.path.is_ident("scan"));
+    let fmt = match attrs.next() {
+        Some(attr) => attr.parse_args()?,
+        None => return Ok(None),
+    };
+    if let Some(attr) = attrs.next() {
+        return Err(Error::new_spanned(attr, "duplicate `scan` attribute"));
+    }
+    Ok(Some(fmt))
+}
+
+/// Expands to the statements scanning `__s` with `fmt` into a `path { .. }`
+/// with `fields`, and to the expression testing if `__c` can start it.
+fn fields(
+    fmt: &LitStr,
+    path: TokenStream,
+    fields: &Fields,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let input = Input::for_fields(fmt, fields)?;
+    let targets = input.verify()?;
+
+    let mut scanned = vec![false; fields.len()];
+    let mut members = Vec::with_capacity(targets.len());
+    for target in &targets {
+        match target {
+            Target::Arg(idx) => {
+                scanned[*idx] = true;
+                members.push(match &fields.iter().nth(*idx).unwrap().ident {
+                    Some(ident) => Member::Named(ident.clone()),
+                    None => Member::Unnamed((*idx).into()),
+                });
+            }
+            Target::Bind(ident) | Target::Capture(ident) => {
+                return Err(Error::new(
+                    fmt.span(),
+                    format!("there is no field named {}", ident),
+                ));
+            }
+        }
+    }
+    if let Some(idx) = scanned.iter().position(|scanned| !scanned) {
+        let field = fields.iter().nth(idx).unwrap();
+        return Err(Error::new(
+            fmt.span(),
+            match &field.ident {
+                Some(ident) => format!("field `{}` is not scanned", ident),
+                None => format!("field `{}` is not scanned", idx),
+            },
+        ));
+    }
+
+    let values = (0..targets.len())
+        .map(|i| format_ident!("__value{}", i, span = Span::mixed_site()))
+        .collect::<Vec<_>>();
+    let types = targets.iter().map(|target| match target {
+        Target::Arg(idx) => &fields.iter().nth(*idx).unwrap().ty,
+        Target::Bind(_) | Target::Capture(_) => unreachable!(),
+    });
+
+    let value = input.expand(&targets, Mode::Value);
+    let scan = quote! {
+        let (#(#values,)*) = #value?;
+        ::core::result::Result::Ok(#path { #(#members: #values),* })
+    };
+    let start = input.expand(
+        &targets,
+        Mode::Start(Box::new(syn::parse_quote!((#(#types,)*)))),
+    );
+    Ok((scan, start))
+}
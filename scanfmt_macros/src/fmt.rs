@@ -1,73 +1,62 @@
 use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::fmt::{self, Display};
 
-use quote::quote;
+use quote::{quote, ToTokens};
 
 use proc_macro2::{Ident, Span, TokenStream};
 
-use syn::{parse::Parse, parse_quote, punctuated::Punctuated, Error, Expr, LitStr, Path, Token};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_quote,
+    punctuated::Punctuated,
+    Error, Expr, Fields, LitStr, Path, Token, Type,
+};
 
-pub fn scan_until_literal(t: &Ident, trait_: &Path, lit: &str) -> Expr {
-    let ch = lit.chars().next().unwrap();
-    parse_quote! {{
-        let mut chars = __string.char_indices();
-        let idx = loop {
-            match chars.next() {
-                ::std::option::Option::Some((idx, c)) if c == #ch => {
-                    break idx;
-                }
-                ::std::option::Option::Some(_) => {}
-                ::std::option::Option::None => return Err(::scanfmt::macro_support::ScanError::Eof)
-            }
-        };
-
-        let (parse, rest) = __string.split_at(idx);
-        let val = <#t as #trait_>::scan(parse)?;
-        (val, rest)
-    }}
-}
-
-pub fn scan_until_scan(current: &Ident, trait_: &Path, next: &Ident, next_trait: &Path) -> Expr {
-    parse_quote! {{
-        let mut chars = __string.char_indices();
-        let idx = loop {
-            match chars.next() {
-                ::std::option::Option::Some((idx, c)) if <#next as #next_trait>::is_valid_start(c) => {
-                    break idx;
-                }
-                ::std::option::Option::Some(_) => {}
-                ::std::option::Option::None => return Err(::scanfmt::macro_support::ScanError::Eof)
-            }
-        };
-
-        let (parse, rest) = __string.split_at(idx);
-        let val = <#current as #trait_>::scan(parse)?;
-        (val, rest)
-    }}
-}
+use scanfmt_syntax::{self as syntax, split_whitespace_runs, CharClass, Spec, Whitespace};
 
+#[derive(Default)]
 pub(crate) enum Argument {
+    #[default]
     Implicit,
+    /// `_`, whose value is scanned and dropped.
+    Discard,
     Named(Ident),
     Index(usize),
 }
 
-pub(crate) enum Spec {
-    Default,
-    Octal,
-    LowerHex,
-    UpperHex,
-    Binary,
-}
-
-#[derive(Default)]
 pub(crate) struct Format {
     argument: Argument,
     spec: Spec,
+    /// The type ascribed to the value, if any.
+    ty: Option<Box<Type>>,
+    /// The number of characters the value spans, if fixed.
+    width: Option<usize>,
+    /// Whether the placeholder is in an optional group, making its value an
+    /// `Option`.
+    optional: bool,
+    /// The separator of a repetition, whose values are collected in a `Vec`.
+    separator: Option<String>,
+    /// The placeholder as written in the format string, including braces.
+    source: String,
 }
 
 pub(crate) enum Piece {
-    Lit(String),
+    Lit(String, Whitespace),
     Fmt(Format),
+    /// The `[` starting an optional group.
+    OptionalStart,
+    /// The `]` ending an optional group.
+    OptionalEnd,
+}
+
+/// Where the value of a placeholder goes.
+pub(crate) enum Target {
+    /// Assigned to the argument with the given index.
+    Arg(usize),
+    /// Bound to a new variable.
+    Bind(Ident),
+    /// Assigned to the variable in scope with the given name.
+    Capture(Ident),
 }
 
 pub(crate) struct FormatString {
@@ -75,12 +64,37 @@ pub(crate) struct FormatString {
     span: Span,
 }
 
+/// What the expansion of the macro evaluates to.
+pub(crate) enum Mode {
+    /// Match the whole input and evaluate to `()`.
+    Full,
+    /// Match a prefix of the input and evaluate to the rest.
+    Prefix,
+    /// Match the whole input and evaluate to a `Result` of the scanned values.
+    Value,
+    /// Evaluate to whether a match can start with the `char` in `__c`, where
+    /// the scanned values have the given tuple type.
+    Start(Box<Type>),
+}
+
+/// An argument to assign a value to: a place expression, optionally named by
+/// `name = place`. Arguments that are plain identifiers are named by them.
+pub(crate) struct Arg {
+    name: Option<(Ident, Token![=])>,
+    place: Expr,
+}
+
 pub(crate) struct Input {
     s: Expr,
     _comma: Token![,],
     fmt: FormatString,
     _comma1: Option<Token![,]>,
-    args: Punctuated<Ident, Token![,]>,
+    args: Punctuated<Arg, Token![,]>,
+    /// A trailing `..`, which allows input to remain after the pattern.
+    rest: Option<Token![..]>,
+    /// The reader that `scanln!`, `scan_read!` and `scan_lines!` read lines
+    /// from, and the variable holding the line, which is `s`.
+    reader: Option<(Expr, Ident)>,
 }
 
 macro_rules! with_dollar_sign {
@@ -95,12 +109,14 @@ macro_rules! decl_macros_with_span {
     ($sp:expr) => {
         with_dollar_sign! {
             ($d:tt) => {
+                #[allow(unused_macros)]
                 macro_rules! err {
                     ($d ($d tt:tt)*) => {
                         Error::new($sp, &format!($d ($d tt)*))
                     }
                 }
 
+                #[allow(unused_macros)]
                 macro_rules! bail {
                     ($d ($d tt:tt)*) => {
                         return Err(err!($d ( $d tt )*))
@@ -112,16 +128,21 @@ macro_rules! decl_macros_with_span {
 }
 
 impl Input {
-    pub fn verify(&self) -> syn::Result<Vec<usize>> {
+    pub fn verify(&self) -> syn::Result<Vec<Target>> {
         let mut idents = HashMap::new();
         for (n, arg) in self.args.iter().enumerate() {
-            match idents.entry(arg.clone()) {
+            let name = match arg.name() {
+                Some(name) => name,
+                None => continue,
+            };
+            match idents.entry(name.clone()) {
                 Entry::Occupied(prev) => {
                     let mut e = Error::new_spanned(arg, "duplicate argument");
                     e.combine(Error::new_spanned(
                         prev.key(),
                         "argument previously defined here",
                     ));
+                    return Err(e);
                 }
                 Entry::Vacant(e) => {
                     e.insert(n);
@@ -132,113 +153,515 @@ impl Input {
         let mut cnt = 0;
 
         let mut indices = HashSet::new();
-        let mut indices_vec = Vec::with_capacity(self.fmt.pieces.len());
+        let mut bindings = HashSet::new();
+        let mut targets = Vec::with_capacity(self.fmt.pieces.len());
 
         decl_macros_with_span!(self.fmt.span);
 
         for p in &self.fmt.pieces {
             if let Piece::Fmt(fmt) = p {
-                let idx = fmt
-                    .argument
-                    .idx(&mut cnt, &idents, &self.args, self.fmt.span)?;
-                if !indices.insert(idx) {
-                    bail!("{} is referenced multiple times", self.args[idx]);
-                }
-                indices_vec.push(idx);
+                let target = match (&fmt.argument, &fmt.ty) {
+                    (Argument::Discard, _) => continue,
+                    // a placeholder that names no argument declares a new variable if it is
+                    // typed, and captures the variable in scope otherwise.
+                    (Argument::Named(id), ty) if !idents.contains_key(id) => {
+                        if !bindings.insert(id) {
+                            bail!("{} is referenced multiple times", id);
+                        }
+                        match ty {
+                            Some(_) => Target::Bind(id.clone()),
+                            None => Target::Capture(id.clone()),
+                        }
+                    }
+                    (argument, _) => {
+                        let idx =
+                            argument.idx(&mut cnt, &idents, self.args.len(), self.fmt.span)?;
+                        if !indices.insert(idx) {
+                            bail!("{} is referenced multiple times", self.args[idx]);
+                        }
+                        Target::Arg(idx)
+                    }
+                };
+                targets.push(target);
             }
         }
 
-        Ok(indices_vec)
+        Ok(targets)
     }
 
-    pub fn expand(self, pieceidx2argidx: &[usize]) -> TokenStream {
-        let exp = self.s;
-        let idents = self.args.iter();
-        let spec_traits = self
+    /// Creates the input for scanning `__s` into `fields`, to which the
+    /// placeholders of `fmt` refer by name or position.
+    pub fn for_fields(fmt: &LitStr, fields: &Fields) -> syn::Result<Self> {
+        let args = fields
+            .iter()
+            .enumerate()
+            .map(|(i, field)| Arg {
+                name: None,
+                place: match &field.ident {
+                    Some(ident) => parse_quote!(#ident),
+                    None => {
+                        let i = syn::Index::from(i);
+                        parse_quote!(#i)
+                    }
+                },
+            })
+            .collect();
+        Ok(Input {
+            s: parse_quote!(__s),
+            _comma: Default::default(),
+            fmt: FormatString::parse(fmt)?,
+            _comma1: None,
+            args,
+            rest: None,
+            reader: None,
+        })
+    }
+
+    pub fn expand(&self, targets: &[Target], mode: Mode) -> TokenStream {
+        let exp = &self.s;
+        let formats = self
             .fmt
             .pieces
             .iter()
-            .filter_map(|p| {
-                if let Piece::Fmt(f) = p {
-                    Some(f.spec.trait_())
-                } else {
-                    None
-                }
-            })
+            .filter_map(|p| if let Piece::Fmt(f) = p { Some(f) } else { None })
+            .collect::<Vec<_>>();
+        let spec_traits = formats
+            .iter()
+            .map(|f| spec_trait(&f.spec))
             .collect::<Vec<_>>();
 
-        let (inference_type_param_idents, temp_var_idents): (Vec<_>, Vec<_>) = (0..pieceidx2argidx
-            .len())
+        let (inference_type_param_idents, temp_var_idents): (Vec<_>, Vec<_>) = (0..formats.len())
             .map(|i| {
                 let var = Ident::new(&format!("__InferredVar{}", i), Span::call_site());
-                let tempvar = Ident::new(&format!("__temp{}", i), Span::call_site());
+                let tempvar = Ident::new(&format!("__temp{}", i), Span::mixed_site());
                 (var, tempvar)
             })
             .unzip();
 
-        let mut fmt_counter = 0;
-
-        let stmts = self
-            .fmt
-            .pieces
+        // discarded values are scanned, but not returned from `__infer_fn`.
+        let kept = formats
             .iter()
             .enumerate()
-            .map(|(i, p)| match p {
-                Piece::Lit(lit) => quote! {
-                    __string = ::scanfmt::macro_support::advance(__string, #lit)?;
-                },
-                Piece::Fmt(_) => {
-                    let t = &inference_type_param_idents[fmt_counter];
-                    let trait_ = &spec_traits[fmt_counter];
-                    let var = &temp_var_idents[fmt_counter];
-                    let tokens = match self.fmt.pieces.get(i + 1) {
-                        Some(Piece::Lit(lit)) => {
-                            let res = scan_until_literal(t, trait_, lit);
+            .filter(|(_, f)| !matches!(f.argument, Argument::Discard))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        // the values of repetitions are stored in vectors, and the others in
+        // options. values in optional groups are returned as they are stored.
+        let storage_types = formats
+            .iter()
+            .zip(&inference_type_param_idents)
+            .map(|(f, t)| match f.separator {
+                Some(_) => quote!(::scanfmt::macro_support::Vec<#t>),
+                None => quote!(::core::option::Option<#t>),
+            })
+            .collect::<Vec<_>>();
+        let kept_types = kept.iter().map(|&i| {
+            let t = &inference_type_param_idents[i];
+            if formats[i].optional || formats[i].separator.is_some() {
+                storage_types[i].clone()
+            } else {
+                quote!(#t)
+            }
+        });
+        let kept_values = kept.iter().map(|&i| {
+            let field = syn::Index::from(i);
+            if formats[i].optional || formats[i].separator.is_some() {
+                quote!(__slots.#field)
+            } else {
+                quote!(__slots.#field.unwrap())
+            }
+        });
+        let temp_var_idents = kept
+            .iter()
+            .map(|&i| &temp_var_idents[i])
+            .collect::<Vec<_>>();
 
-                            quote! {
-                                let (#var, __string_next) = #res;
-                                __string = __string_next;
-                            }
-                        }
-                        Some(Piece::Fmt(_)) => {
-                            let next = &inference_type_param_idents[fmt_counter + 1];
-                            let next_trait = &spec_traits[fmt_counter + 1];
-                            let res = scan_until_scan(t, trait_, next, next_trait);
+        let mut fmt_counter = 0usize;
+        let mut piece_counter = 0usize;
+        let mut instrs = Vec::new();
+        // indices of the `Optional` instructions of the open groups, which are
+        // filled in once the end of the group is known.
+        let mut groups = Vec::new();
 
+        for p in &self.fmt.pieces {
+            let i = piece_counter;
+            match p {
+                Piece::OptionalStart => {
+                    groups.push(instrs.len());
+                    instrs.push(TokenStream::new());
+                    continue;
+                }
+                Piece::OptionalEnd => {
+                    let start = groups.pop().unwrap();
+                    let end = instrs.len();
+                    instrs[start] = quote!(::scanfmt::macro_support::Instr::Optional { end: #end });
+                    continue;
+                }
+                Piece::Lit(lit, ws) => {
+                    let piece = piece_tokens(i, lit);
+                    match ws {
+                        Whitespace::Exact => instrs.push(quote! {
+                            ::scanfmt::macro_support::Instr::Literal { text: ::scanfmt::macro_support::Cow::Borrowed(#lit), piece: #piece }
+                        }),
+                        Whitespace::Flexible(min) => {
+                            instrs.extend(split_whitespace_runs(lit).map(|(text, is_ws)| {
+                                if is_ws {
+                                    quote! {
+                                        ::scanfmt::macro_support::Instr::Whitespace {
+                                            min: #min,
+                                            text: ::scanfmt::macro_support::Cow::Borrowed(#text),
+                                            piece: #piece,
+                                        }
+                                    }
+                                } else {
+                                    quote! {
+                                        ::scanfmt::macro_support::Instr::Literal { text: ::scanfmt::macro_support::Cow::Borrowed(#text), piece: #piece }
+                                    }
+                                }
+                            }))
+                        }
+                    }
+                }
+                Piece::Fmt(fmt) => {
+                    let piece = piece_tokens(i, &fmt.source);
+                    let slot = fmt_counter;
+                    fmt_counter += 1;
+                    let width = match fmt.width {
+                        Some(width) => quote!(::core::option::Option::Some(#width)),
+                        None => quote!(::core::option::Option::None),
+                    };
+                    let class = match &fmt.spec {
+                        Spec::Class(CharClass {
+                            negated,
+                            ranges,
+                            source,
+                        }) => {
+                            let (starts, ends): (Vec<_>, Vec<_>) = ranges.iter().copied().unzip();
                             quote! {
-                                let (#var, __string_next) = #res;
-                                __string = __string_next;
+                                ::core::option::Option::Some(::scanfmt::macro_support::CharClass::new(
+                                    #negated,
+                                    &[#((#starts, #ends)),*],
+                                    #source,
+                                ))
                             }
                         }
-                        None => quote!(let #var = <#t as #trait_>::scan(__string)?;),
+                        _ => quote!(::core::option::Option::None),
                     };
-                    fmt_counter += 1;
-                    tokens
+                    let field = quote! {
+                        ::scanfmt::macro_support::Instr::Field {
+                            slot: #slot,
+                            piece: #piece,
+                            width: #width,
+                            class: #class,
+                        }
+                    };
+                    match &fmt.separator {
+                        // a repetition has any number of values, including none.
+                        Some(separator) => {
+                            let start = instrs.len() + 1;
+                            let end = start + 2;
+                            instrs.extend([
+                                quote!(::scanfmt::macro_support::Instr::Optional { end: #end }),
+                                field,
+                                quote! {
+                                    ::scanfmt::macro_support::Instr::Repeat {
+                                        separator: ::scanfmt::macro_support::Cow::Borrowed(#separator),
+                                        start: #start,
+                                    }
+                                },
+                            ]);
+                        }
+                        None => instrs.push(field),
+                    }
                 }
+            }
+            piece_counter += 1;
+        }
+        if let Mode::Full | Mode::Value | Mode::Start(_) = mode {
+            if self.rest.is_none() {
+                instrs.push(quote!(::scanfmt::macro_support::Instr::End));
+            }
+        }
+
+        let slots = (0..formats.len()).collect::<Vec<_>>();
+        let fields = slots
+            .iter()
+            .map(|&i| syn::Index::from(i))
+            .collect::<Vec<_>>();
+        let prefers_longest = formats.iter().zip(&inference_type_param_idents).map(
+            |(f, t)| match f.spec {
+                Spec::Default => {
+                    quote!(<#t as ::scanfmt::macro_support::ScanBorrowed<'__a>>::prefers_longest())
+                }
+                _ => quote!(true),
+            },
+        );
+        let empty = formats.iter().map(|f| match f.separator {
+            Some(_) => quote!(::scanfmt::macro_support::Vec::new()),
+            None => quote!(::core::option::Option::None),
+        });
+        let (stores, clears): (Vec<_>, Vec<_>) = formats
+            .iter()
+            .zip(&fields)
+            .map(|(f, field)| match f.separator {
+                Some(_) => (quote!(self.#field.push), quote!(self.#field.pop();)),
+                None => (
+                    quote!(self.#field = ::core::option::Option::Some),
+                    quote!(self.#field = ::core::option::Option::None;),
+                ),
+            })
+            .unzip();
+
+        let types = formats
+            .iter()
+            .map(|f| match (&f.ty, &f.argument) {
+                (Some(ty), _) => quote!(#ty),
+                (None, Argument::Discard) => quote!(::scanfmt::macro_support::Discard),
+                (None, _) => quote!(_),
             })
             .collect::<Vec<_>>();
+        let call = quote! {
+            match &#exp {
+                __string => __infer_fn::<#(#types),*>(*__string),
+            }
+        };
+
+        let items = quote! {
+                struct __Slots< #(#inference_type_param_idents),* >(
+                    #(#storage_types,)*
+                );
+
+                impl<'__a, #(#inference_type_param_idents: #spec_traits),* > ::scanfmt::macro_support::Slots<'__a>
+                    for __Slots< #(#inference_type_param_idents),* >
+                {
+                    fn is_valid_start(&self, __slot: usize, __c: char) -> bool {
+                        match __slot {
+                            #(#slots => <#inference_type_param_idents as #spec_traits>::is_valid_start(__c),)*
+                            _ => ::core::unreachable!(),
+                        }
+                    }
+
+                    fn prefers_longest(&self, __slot: usize) -> bool {
+                        match __slot {
+                            #(#slots => #prefers_longest,)*
+                            _ => ::core::unreachable!(),
+                        }
+                    }
+
+                    fn scan(
+                        &mut self,
+                        __slot: usize,
+                        __s: &'__a str,
+                    ) -> ::core::result::Result<(), ::scanfmt::macro_support::ScanError> {
+                        match __slot {
+                            #(#slots => {
+                                #stores(<#inference_type_param_idents as #spec_traits>::scan(__s)?);
+                                ::core::result::Result::Ok(())
+                            })*
+                            _ => ::core::unreachable!(),
+                        }
+                    }
 
-        quote! {{
-            fn __infer_fn< #(#inference_type_param_idents: #spec_traits),* >(
-                mut __string: &str
-            ) -> ::std::result::Result<(#(#inference_type_param_idents,)*), ::scanfmt::macro_support::ScanError> {
-                #(#stmts)*
+                    fn clear(&mut self, __slot: usize) {
+                        match __slot {
+                            #(#slots => {
+                                #clears
+                            })*
+                            _ => ::core::unreachable!(),
+                        }
+                    }
+                }
+
+                static __PROGRAM: &[::scanfmt::macro_support::Instr] = &[#(#instrs),*];
+        };
+
+        if let Mode::Start(values) = mode {
+            return quote! {{
+                fn __start_fn<'__a, #(#inference_type_param_idents: #spec_traits),* >(
+                    __c: char,
+                    _: ::core::marker::PhantomData<fn() -> (#(#kept_types,)*)>,
+                ) -> bool {
+                    #items
+                    let __slots: __Slots< #(#inference_type_param_idents),* > = __Slots(#(#empty,)*);
+                    ::scanfmt::macro_support::is_valid_start(__PROGRAM, &__slots, __c)
+                }
+                __start_fn::<#(#types),*>(__c, ::core::marker::PhantomData::<fn() -> #values>)
+            }};
+        }
+
+        let infer_fn = quote! {
+            fn __infer_fn<'__a, #(#inference_type_param_idents: #spec_traits),* >(
+                __string: &'__a str
+            ) -> ::core::result::Result<
+                ((#(#kept_types,)*), &'__a str),
+                ::scanfmt::macro_support::ScanError,
+            > {
+                #items
+
+                let mut __slots: __Slots< #(#inference_type_param_idents),* > = __Slots(#(#empty,)*);
+                let __end = ::scanfmt::macro_support::run(__PROGRAM, __string, &mut __slots)?;
+                ::core::result::Result::Ok(((#(#kept_values,)*), &__string[__end..]))
+            }
+        };
 
-                Ok((#(#temp_var_idents,)*))
+        let (rest, value) = match mode {
+            Mode::Full => (quote!(_), quote!()),
+            Mode::Prefix => {
+                let rest = Ident::new("__rest", Span::mixed_site());
+                (quote!(#rest), quote!(#rest))
             }
+            Mode::Value => {
+                let value = quote! {{
+                    #infer_fn
+                    #call.map(|(__values, _)| __values)
+                }};
+                // `scan_lines!` scans each line read from the reader.
+                return match &self.reader {
+                    Some((reader, line)) => quote! {
+                        ::scanfmt::macro_support::lines(#reader, |#line: &str| #value)
+                    },
+                    None => value,
+                };
+            }
+            Mode::Start(_) => unreachable!(),
+        };
 
-            let ( #(#temp_var_idents,)* ) = match &#exp {
-                __string => __infer_fn(*__string),
+        let assignments = targets
+            .iter()
+            .zip(&temp_var_idents)
+            .map(|(target, temp)| match target {
+                Target::Arg(idx) => {
+                    let place = &self.args[*idx].place;
+                    quote!(#place = #temp;)
+                }
+                Target::Bind(id) => quote!(let #id = #temp;),
+                Target::Capture(id) => quote!(#id = #temp;),
+            });
+        let read = self.reader.as_ref().map(|(reader, line)| {
+            quote! {
+                let #line: &str = &{
+                    use ::scanfmt::macro_support::ReadLine as _;
+                    (#reader).__scanfmt_read_line()
+                }?;
+            }
+        });
+        let stmts = quote! {
+            #read
+            let (( #(#temp_var_idents,)* ), #rest) = {
+                #infer_fn
+                #call
             }?;
-            #(
-                #idents = #temp_var_idents;
-            )*
-        }}
+            #(#assignments)*
+        };
+
+        // new variables must be declared in the scope of the caller.
+        if targets.iter().any(|t| matches!(t, Target::Bind(_))) {
+            stmts
+        } else {
+            quote! {{
+                #stmts
+                #value
+            }}
+        }
     }
 
-    pub fn verify_and_expand(self) -> syn::Result<TokenStream> {
-        let indices = self.verify()?;
-        Ok(self.expand(&indices))
+    pub fn verify_and_expand(self, mode: Mode) -> syn::Result<TokenStream> {
+        if let (Mode::Prefix, Some(rest)) = (&mode, &self.rest) {
+            return Err(Error::new_spanned(
+                rest,
+                "`..` is redundant, `scanfmt_prefix!` always matches a prefix",
+            ));
+        }
+        let targets = match mode {
+            Mode::Value | Mode::Start(_) => {
+                self.verify_values()?;
+                Vec::new()
+            }
+            Mode::Full | Mode::Prefix => self.verify()?,
+        };
+        if let Mode::Prefix = mode {
+            for target in &targets {
+                if let Target::Bind(id) = target {
+                    return Err(Error::new_spanned(
+                        id,
+                        format!(
+                            "`scanfmt_prefix!` cannot declare `{}`, declare it beforehand and pass it as an argument",
+                            id
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(self.expand(&targets, mode))
+    }
+
+    /// Verifies the input of `scan!`, whose placeholders are returned in order
+    /// instead of being assigned to arguments.
+    fn verify_values(&self) -> syn::Result<()> {
+        if let Some(arg) = self.args.first() {
+            return Err(Error::new_spanned(
+                arg,
+                "`scan!` takes no arguments, it evaluates to the scanned values",
+            ));
+        }
+
+        decl_macros_with_span!(self.fmt.span);
+
+        for p in &self.fmt.pieces {
+            if let Piece::Fmt(fmt) = p {
+                if let Argument::Index(_) = fmt.argument {
+                    bail!("`scan!` does not support positional arguments");
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Arg {
+    /// The name by which placeholders can refer to this argument.
+    fn name(&self) -> Option<&Ident> {
+        match (&self.name, &self.place) {
+            (Some((name, _)), _) => Some(name),
+            (None, Expr::Path(path)) if path.attrs.is_empty() && path.qself.is_none() => {
+                path.path.get_ident()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Parse for Arg {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let name = if input.peek(syn::Ident) && input.peek2(Token![=]) && !input.peek2(Token![==]) {
+            Some((input.parse()?, input.parse()?))
+        } else {
+            None
+        };
+        Ok(Arg {
+            name,
+            place: input.parse()?,
+        })
+    }
+}
+
+impl ToTokens for Arg {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        if let Some((name, eq)) = &self.name {
+            name.to_tokens(tokens);
+            eq.to_tokens(tokens);
+        }
+        self.place.to_tokens(tokens);
+    }
+}
+
+impl Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => name.fmt(f),
+            None => self.place.to_token_stream().fmt(f),
+        }
     }
 }
 
@@ -247,12 +670,12 @@ impl Argument {
         &self,
         counter: &mut usize,
         map: &HashMap<Ident, usize>,
-        p: &Punctuated<Ident, Token![,]>,
+        len: usize,
         sp: Span,
     ) -> syn::Result<usize> {
         decl_macros_with_span!(sp);
         let within_bounds = |n, s| {
-            if n >= p.len() {
+            if n >= len {
                 bail!("{} index {} is out of bounds", s, n)
             }
 
@@ -265,6 +688,7 @@ impl Argument {
                 within_bounds(c, "implicit")
             }
             Argument::Index(c) => within_bounds(*c, "specified"),
+            Argument::Discard => unreachable!("discarded values have no argument"),
             Argument::Named(id) => map
                 .get(id)
                 .copied()
@@ -273,131 +697,116 @@ impl Argument {
     }
 }
 
-impl Format {
-    fn parse_within_braces(s: &str, sp: Span) -> syn::Result<Self> {
-        decl_macros_with_span!(sp);
-        if s.is_empty() {
-            return Ok(Format::default());
-        }
-        // non-empty string;
-        let col = s.find(':').unwrap_or_else(|| s.len());
-        let (ident, col) = s.split_at(col);
-        let col = &col[1..];
-
-        let argument = if ident.is_empty() {
-            Argument::Implicit
-        } else if let Ok(index) = ident.parse() {
-            Argument::Index(index)
-        } else {
-            Argument::Named(Ident::new(ident, sp))
-        };
-
-        let spec = if col.is_empty() {
-            Spec::Default
-        } else {
-            match col.chars().next() {
-                Some('o') => Spec::Octal,
-                Some('x') => Spec::LowerHex,
-                Some('X') => Spec::UpperHex,
-                Some('b') => Spec::Binary,
-                None => bail!("expected spec after :"),
-                Some(c) => bail!(
-                    "expected one of 'o', 'x', 'X', or 'b' after ':', found {}",
-                    c
-                ),
-            }
-        };
-
-        Ok(Self { argument, spec })
-    }
+fn parse_type(s: &str, sp: Span) -> syn::Result<Box<Type>> {
+    decl_macros_with_span!(sp);
+    syn::parse_str(s).map_err(|_| {
+        err!(
+            "expected one of 'o', 'x', 'X', or 'b', or a type after ':', found `{}`",
+            s
+        )
+    })
 }
 
 impl FormatString {
     fn parse(s: &LitStr) -> syn::Result<Self> {
-        use std::mem::take;
-
         let span = s.span();
-        decl_macros_with_span!(span);
-
-        let s = s.value();
-        let mut pieces = vec![];
-        let mut next_lit = String::new();
-        let mut chars = s.char_indices().peekable();
-        let mut brace_start = None;
-        loop {
-            match chars.next() {
-                Some((n, '}')) => match chars.peek() {
-                    Some((_, '}')) => {
-                        chars.next();
-                        next_lit.push('}');
-                    }
-                    _ => match brace_start.take() {
-                        Some(n_prev) => {
-                            if !next_lit.is_empty() {
-                                pieces.push(Piece::Lit(take(&mut next_lit)));
-                            }
-                            pieces.push(Piece::Fmt(Format::parse_within_braces(
-                                &s[n_prev + 1..n],
-                                span,
-                            )?));
-                        }
-                        None => bail!("mismatched '}}' with no opening braces, index={}", n),
-                    },
-                },
-                Some((n, '{')) => {
-                    if let Some(n_prev) = brace_start {
-                        bail!(
-                            "attempt to start index={} while prev_index={} is not closed",
-                            n,
-                            n_prev,
-                        )
-                    }
-                    match chars.peek() {
-                        Some((_, '{')) => {
-                            chars.next();
-                            next_lit.push('{');
-                        }
-                        _ => brace_start = Some(n),
-                    }
-                }
-                Some(_) if brace_start.is_some() => {}
-                Some((_, c)) => {
-                    next_lit.push(c);
-                }
-                None if brace_start.is_some() => {
-                    bail!("mismatched '{{' for index={}", brace_start.unwrap())
-                }
-                None if !next_lit.is_empty() => {
-                    pieces.push(Piece::Lit(take(&mut next_lit)));
-                    break;
-                }
-                None => break,
-            }
-        }
+        let pieces = scanfmt_syntax::parse(&s.value())
+            .map_err(|e| Error::new(span, e))?
+            .into_iter()
+            .map(|piece| {
+                Ok(match piece {
+                    syntax::Piece::Lit(lit, ws) => Piece::Lit(lit, ws),
+                    syntax::Piece::Fmt(fmt) => Piece::Fmt(Format::from_syntax(fmt, span)?),
+                    syntax::Piece::OptionalStart => Piece::OptionalStart,
+                    syntax::Piece::OptionalEnd => Piece::OptionalEnd,
+                })
+            })
+            .collect::<syn::Result<_>>()?;
 
         Ok(FormatString { pieces, span })
     }
 }
 
-impl Spec {
-    pub fn trait_(&self) -> Path {
-        match self {
-            Spec::Default => parse_quote!(::scanfmt::macro_support::Scan),
-            Spec::Binary => parse_quote!(::scanfmt::macro_support::ScanBinary),
-            Spec::Octal => parse_quote!(::scanfmt::macro_support::ScanOctal),
-            Spec::LowerHex => parse_quote!(::scanfmt::macro_support::ScanLowerHex),
-            Spec::UpperHex => parse_quote!(::scanfmt::macro_support::ScanUpperHex),
+impl Format {
+    /// Converts a parsed placeholder, parsing its argument name and type.
+    fn from_syntax(fmt: syntax::Format, sp: Span) -> syn::Result<Self> {
+        decl_macros_with_span!(sp);
+        let argument = match fmt.argument {
+            syntax::Argument::Implicit => Argument::Implicit,
+            syntax::Argument::Discard => Argument::Discard,
+            syntax::Argument::Index(index) => Argument::Index(index),
+            syntax::Argument::Named(name) => {
+                let mut ident = syn::parse_str::<Ident>(&name)
+                    .map_err(|_| err!("expected an argument name or index, found `{}`", name))?;
+                ident.set_span(sp);
+                Argument::Named(ident)
+            }
+        };
+        let ty = fmt.ty.map(|ty| parse_type(&ty, sp)).transpose()?;
+
+        Ok(Format {
+            argument,
+            spec: fmt.spec,
+            ty,
+            width: fmt.width,
+            optional: fmt.optional,
+            separator: fmt.separator,
+            source: fmt.source,
+        })
+    }
+}
+
+/// Tokens constructing the `Piece` at index `i` with the given source text.
+fn piece_tokens(i: usize, source: &str) -> TokenStream {
+    quote!(::scanfmt::macro_support::Piece::new(#i, #source))
+}
+
+/// The trait that values with the given spec are scanned with, from input
+/// that lives for `'__a`.
+fn spec_trait(spec: &Spec) -> Path {
+    match spec {
+        Spec::Default | Spec::Class(_) => {
+            parse_quote!(::scanfmt::macro_support::ScanBorrowed<'__a>)
         }
+        Spec::Binary => parse_quote!(::scanfmt::macro_support::ScanBinary),
+        Spec::Octal => parse_quote!(::scanfmt::macro_support::ScanOctal),
+        Spec::LowerHex => parse_quote!(::scanfmt::macro_support::ScanLowerHex),
+        Spec::UpperHex => parse_quote!(::scanfmt::macro_support::ScanUpperHex),
     }
 }
 
-impl Parse for Input {
-    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
-        let s = input.parse()?;
-        let _comma = input.parse()?;
+impl Input {
+    /// Parses the input of `scanln!`, `scan_read!` and `scan_lines!`, which is
+    /// the input of `scanfmt!` or `scan!` without the string to scan, since
+    /// that is the line read from `reader`.
+    pub fn parse_read(reader: Expr, input: ParseStream) -> syn::Result<Self> {
+        let line = Ident::new("__line", Span::mixed_site());
+        let s = parse_quote!(#line);
+        let mut input = Self::parse_after_input(s, Default::default(), input)?;
+        input.reader = Some((reader, line));
+        Ok(input)
+    }
+
+    fn parse_after_input(s: Expr, _comma: Token![,], input: ParseStream) -> syn::Result<Self> {
         let fmt: LitStr = input.parse()?;
         let _comma1 = input.parse()?;
-        let args = input.parse_terminated(Ident::parse)?;
+        let mut args = Punctuated::new();
+        let mut rest = None;
+        while !input.is_empty() {
+            if input.peek(Token![..]) {
+                rest = Some(input.parse()?);
+                let _: Option<Token![,]> = input.parse()?;
+                if !input.is_empty() {
+                    return Err(input.error("`..` must be the last argument"));
+                }
+                break;
+            }
+            args.push_value(input.parse()?);
+            if input.is_empty() {
+                break;
+            }
+            args.push_punct(input.parse()?);
+        }
 
         Ok(Input {
             s,
@@ -405,18 +814,16 @@ impl Parse for Input {
             fmt: FormatString::parse(&fmt)?,
             _comma1,
             args,
+            rest,
+            reader: None,
         })
     }
 }
 
-impl Default for Spec {
-    fn default() -> Self {
-        Self::Default
-    }
-}
-
-impl Default for Argument {
-    fn default() -> Self {
-        Self::Implicit
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let s = input.parse()?;
+        let _comma = input.parse()?;
+        Self::parse_after_input(s, _comma, input)
     }
 }
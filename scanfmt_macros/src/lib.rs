@@ -1,18 +1,97 @@
 use proc_macro::TokenStream as Ts;
 use proc_macro2::TokenStream as Ts2;
-use syn::parse2;
+use syn::parse::{ParseStream, Parser};
+use syn::{parse2, parse_macro_input, parse_quote, DeriveInput, Expr, Token};
 
+mod derive;
 mod fmt;
 
+use fmt::Mode;
+
 #[proc_macro]
 pub fn scanfmt(input: Ts) -> Ts {
-    scanfmt_inner(input.into())
+    expand(input, Mode::Full)
+}
+
+/// Like `scanfmt!`, but only matches the start of the input and evaluates to
+/// the rest of it.
+#[proc_macro]
+pub fn scanfmt_prefix(input: Ts) -> Ts {
+    expand(input, Mode::Prefix)
+}
+
+/// Scans the input and evaluates to a `Result` of a tuple with the values of
+/// the placeholders, in order. Named placeholders only label the values.
+#[proc_macro]
+pub fn scan(input: Ts) -> Ts {
+    expand(input, Mode::Value)
+}
+
+/// Like `scanfmt!`, but reads a line from standard input and scans it
+/// without its line terminator, so it takes no input argument.
+#[proc_macro]
+pub fn scanln(input: Ts) -> Ts {
+    let stdin = parse_quote!(::std::io::stdin().lock());
+    expand_read(input, Mode::Full, |input: ParseStream| {
+        fmt::Input::parse_read(stdin, input)
+    })
+}
+
+/// Like `scanln!`, but reads the line from the `BufRead` given as the first
+/// argument.
+#[proc_macro]
+pub fn scan_read(input: Ts) -> Ts {
+    expand_read(input, Mode::Full, |input: ParseStream| {
+        let reader: Expr = input.parse()?;
+        let _: Token![,] = input.parse()?;
+        fmt::Input::parse_read(reader, input)
+    })
+}
+
+/// Like `scan!`, but evaluates to an iterator over the values scanned from
+/// each line of the `BufRead` given as the first argument, without their line
+/// terminators.
+#[proc_macro]
+pub fn scan_lines(input: Ts) -> Ts {
+    expand_read(input, Mode::Value, |input: ParseStream| {
+        let reader: Expr = input.parse()?;
+        let _: Token![,] = input.parse()?;
+        fmt::Input::parse_read(reader, input)
+    })
+}
+
+/// Derives `Scan` for a struct from the format string in its `#[scan("...")]`
+/// attribute, whose placeholders refer to the fields by name or position.
+///
+/// The variants of an enum have format strings of their own, and are tried in
+/// order. Unit variants match their name by default.
+///
+/// Types with a lifetime parameter, whose fields may borrow from the input for
+/// it, implement `ScanBorrowed` instead.
+#[proc_macro_derive(Scan, attributes(scan))]
+pub fn derive_scan(input: Ts) -> Ts {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::derive_scan(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: Ts, mode: Mode) -> Ts {
+    scanfmt_inner(input.into(), mode)
         .map(Into::into)
         .map_err(syn::Error::into_compile_error)
         .unwrap_or_else(Into::into)
 }
 
-fn scanfmt_inner(input: Ts2) -> syn::Result<Ts2> {
+fn expand_read(input: Ts, mode: Mode, parser: impl Parser<Output = fmt::Input>) -> Ts {
+    parser
+        .parse(input)
+        .and_then(|input| input.verify_and_expand(mode))
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn scanfmt_inner(input: Ts2, mode: Mode) -> syn::Result<Ts2> {
     let input: fmt::Input = parse2(input)?;
-    input.verify_and_expand()
+    input.verify_and_expand(mode)
 }
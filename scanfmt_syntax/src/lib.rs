@@ -0,0 +1,506 @@
+//! The syntax of the format strings of `scanfmt`, which is shared by its
+//! macros and its runtime patterns.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::borrow::ToOwned;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+use core::mem::take;
+
+/// An error in a format string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    message: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Argument {
+    #[default]
+    Implicit,
+    /// `_`, whose value is scanned and dropped.
+    Discard,
+    Named(String),
+    Index(usize),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum Spec {
+    #[default]
+    Default,
+    /// The value is the longest run of characters in the class.
+    Class(CharClass),
+    Octal,
+    LowerHex,
+    UpperHex,
+    Binary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharClass {
+    pub negated: bool,
+    pub ranges: Vec<(char, char)>,
+    /// The class as written in the format string, including brackets.
+    pub source: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format {
+    pub argument: Argument,
+    pub spec: Spec,
+    /// The type ascribed to the value, if any, as written.
+    pub ty: Option<String>,
+    /// The number of characters the value spans, if fixed.
+    pub width: Option<usize>,
+    /// Whether the placeholder is in an optional group, making its value an
+    /// `Option`.
+    pub optional: bool,
+    /// The separator of a repetition, whose values are collected in a `Vec`.
+    pub separator: Option<String>,
+    /// The placeholder as written in the format string, including braces.
+    pub source: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Piece {
+    Lit(String, Whitespace),
+    Fmt(Format),
+    /// The `[` starting an optional group.
+    OptionalStart,
+    /// The `]` ending an optional group.
+    OptionalEnd,
+}
+
+/// How whitespace in a literal matches the input, as set by the `{!ws}` and
+/// `{!ws+}` directives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Whitespace {
+    /// Whitespace matches itself.
+    Exact,
+    /// A run of whitespace matches at least this many whitespace characters.
+    Flexible(usize),
+}
+
+macro_rules! bail {
+    ($($tt:tt)*) => {
+        return Err(Error::new(format!($($tt)*)))
+    };
+}
+
+impl Error {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Parses a format string into its pieces.
+pub fn parse(s: &str) -> Result<Vec<Piece>, Error> {
+    let mut pieces = Vec::new();
+    let mut next_lit = String::new();
+    let mut chars = s.char_indices().peekable();
+    let mut brace_start = None;
+    let mut ws = Whitespace::Exact;
+    // the indices of the `[` of the open optional groups, and of their first
+    // pieces.
+    let mut groups = Vec::new();
+    loop {
+        match chars.next() {
+            Some((n, '}')) => match chars.peek() {
+                Some((_, '}')) => {
+                    chars.next();
+                    next_lit.push('}');
+                }
+                _ => match brace_start.take() {
+                    Some(n_prev) => {
+                        if !next_lit.is_empty() {
+                            pieces.push(Piece::Lit(take(&mut next_lit), ws));
+                        }
+                        let inner = &s[n_prev + 1..n];
+                        if let Some(directive) = inner.strip_prefix('!') {
+                            ws = match directive {
+                                "ws" => Whitespace::Flexible(0),
+                                "ws+" => Whitespace::Flexible(1),
+                                _ => bail!("unknown directive `{}`", inner),
+                            };
+                            continue;
+                        }
+                        let mut fmt = Format::parse_within_braces(inner)?;
+                        fmt.optional = !groups.is_empty();
+                        pieces.push(Piece::Fmt(fmt));
+                    }
+                    None => bail!("mismatched '}}' with no opening braces, index={}", n),
+                },
+            },
+            Some((n, '{')) => {
+                if let Some(n_prev) = brace_start {
+                    bail!(
+                        "attempt to start index={} while prev_index={} is not closed",
+                        n,
+                        n_prev,
+                    )
+                }
+                match chars.peek() {
+                    Some((_, '{')) => {
+                        chars.next();
+                        next_lit.push('{');
+                    }
+                    _ => brace_start = Some(n),
+                }
+            }
+            // a class or a separator may contain braces, which do not end
+            // the placeholder.
+            Some((n, '[')) if brace_start.is_some() => {
+                if let Ok((_, len)) = CharClass::parse(&s[n..]) {
+                    while chars.next_if(|&(i, _)| i < n + len).is_some() {}
+                }
+            }
+            Some((n, '"')) if brace_start.is_some() => {
+                if let Some(len) = quoted_len(&s[n..]) {
+                    while chars.next_if(|&(i, _)| i < n + len).is_some() {}
+                }
+            }
+            Some(_) if brace_start.is_some() => {}
+            Some((_, '[')) if chars.next_if(|&(_, c)| c == '[').is_some() => {
+                next_lit.push('[');
+            }
+            Some((_, ']')) if chars.next_if(|&(_, c)| c == ']').is_some() => {
+                next_lit.push(']');
+            }
+            Some((n, '[')) => {
+                if !next_lit.is_empty() {
+                    pieces.push(Piece::Lit(take(&mut next_lit), ws));
+                }
+                groups.push((n, pieces.len()));
+                pieces.push(Piece::OptionalStart);
+            }
+            Some((n, ']')) => {
+                let Some((start, first)) = groups.pop() else {
+                    bail!("mismatched ']' with no opening bracket, index={}", n);
+                };
+                if !next_lit.is_empty() {
+                    pieces.push(Piece::Lit(take(&mut next_lit), ws));
+                }
+                if let Some(Piece::OptionalStart) = pieces.last() {
+                    bail!("empty optional group, index={}", n);
+                }
+                // brackets were literal before groups were added, so a group
+                // without a value is most likely meant to be literal text.
+                if !pieces[first..].iter().any(|p| matches!(p, Piece::Fmt(_))) {
+                    bail!(
+                        "optional group `{}` has no placeholders, use `[[` and `]]` for literal brackets",
+                        &s[start..=n]
+                    );
+                }
+                pieces.push(Piece::OptionalEnd);
+            }
+            Some((_, c)) => {
+                next_lit.push(c);
+            }
+            None if brace_start.is_some() => {
+                bail!("mismatched '{{' for index={}", brace_start.unwrap())
+            }
+            None if !groups.is_empty() => {
+                bail!("mismatched '[' for index={}", groups.last().unwrap().0)
+            }
+            None if !next_lit.is_empty() => {
+                pieces.push(Piece::Lit(take(&mut next_lit), ws));
+                break;
+            }
+            None => break,
+        }
+    }
+
+    Ok(pieces)
+}
+
+impl Format {
+    fn parse_within_braces(s: &str) -> Result<Self, Error> {
+        let source = format!("{{{}}}", s);
+        if s.is_empty() {
+            return Ok(Format {
+                argument: Argument::default(),
+                spec: Spec::default(),
+                ty: None,
+                width: None,
+                optional: false,
+                separator: None,
+                source,
+            });
+        }
+        // non-empty string;
+        let col = s.find(':').unwrap_or(s.len());
+        let (ident, col) = s.split_at(col);
+        let ident = ident.trim();
+        let col = col.strip_prefix(':').unwrap_or(col).trim();
+
+        let argument = if ident.is_empty() {
+            Argument::Implicit
+        } else if ident == "_" {
+            Argument::Discard
+        } else if let Ok(index) = ident.parse() {
+            Argument::Index(index)
+        } else if is_identifier(ident) {
+            Argument::Named(ident.to_owned())
+        } else {
+            bail!("expected an argument name or index, found `{}`", ident)
+        };
+
+        let (separator, col) = match col.strip_prefix('*') {
+            Some(rest) => {
+                let (separator, len) = parse_separator(rest)?;
+                (Some(separator), rest[len..].trim_start())
+            }
+            None => (None, col),
+        };
+
+        let digits = col.find(|c: char| !c.is_ascii_digit()).unwrap_or(col.len());
+        let (width, col) = col.split_at(digits);
+        let width = match width {
+            "" => None,
+            width => match width.parse() {
+                Ok(0) | Err(_) => bail!("invalid width `{}`", width),
+                Ok(width) => Some(width),
+            },
+        };
+        let (spec, ty) = Self::parse_spec(col.trim_start())?;
+
+        Ok(Self {
+            argument,
+            spec,
+            ty,
+            width,
+            optional: false,
+            separator,
+            source,
+        })
+    }
+
+    /// Parses what follows the `:`, which is either a radix or a character
+    /// class optionally followed by `as` and a type, or just a type.
+    fn parse_spec(s: &str) -> Result<(Spec, Option<String>), Error> {
+        if s.is_empty() {
+            return Ok((Spec::Default, None));
+        }
+
+        if s.starts_with('[') {
+            let (class, len) = CharClass::parse(s)?;
+            return match s[len..].trim_start() {
+                "" => Ok((Spec::Class(class), None)),
+                ty => match ty.strip_prefix("as") {
+                    Some(ty) if ty.starts_with(char::is_whitespace) => {
+                        Ok((Spec::Class(class), Some(ty.trim_start().to_owned())))
+                    }
+                    _ => bail!("expected `as` and a type after the class, found `{}`", ty),
+                },
+            };
+        }
+
+        let (head, tail) = match s.find(char::is_whitespace) {
+            Some(idx) => (&s[..idx], s[idx..].trim_start()),
+            None => (s, ""),
+        };
+
+        let (spec, ty) = match head {
+            "o" => (Spec::Octal, tail),
+            "x" => (Spec::LowerHex, tail),
+            "X" => (Spec::UpperHex, tail),
+            "b" => (Spec::Binary, tail),
+            "as" => (Spec::Default, s),
+            _ => return Ok((Spec::Default, Some(s.to_owned()))),
+        };
+
+        if ty.is_empty() {
+            return Ok((spec, None));
+        }
+        match ty.strip_prefix("as") {
+            Some(ty) if ty.starts_with(char::is_whitespace) => {
+                Ok((spec, Some(ty.trim_start().to_owned())))
+            }
+            _ => bail!("expected `as` and a type after the radix, found `{}`", ty),
+        }
+    }
+}
+
+impl CharClass {
+    /// Parses the character class at the start of `s`, returning it and its
+    /// length in bytes.
+    ///
+    /// A `^` after the opening bracket negates the class, and a `]` right after
+    /// that is part of the class. A `\` escapes the character after it.
+    pub fn parse(s: &str) -> Result<(Self, usize), Error> {
+        let mut chars = s.char_indices().skip(1).peekable();
+        let negated = chars.next_if(|&(_, c)| c == '^').is_some();
+        let mut ranges = Vec::new();
+        let mut first = true;
+
+        let next = |chars: &mut core::iter::Peekable<_>| match chars.next() {
+            Some((_, '\\')) => chars.next().map(|(_, c)| c),
+            Some((_, c)) => Some(c),
+            None => None,
+        };
+        let unterminated = || Error::new(format!("unterminated character class `{}`", s));
+
+        loop {
+            match chars.peek() {
+                Some(&(n, ']')) if !first => {
+                    let len = n + 1;
+                    return Ok((
+                        CharClass {
+                            negated,
+                            ranges,
+                            source: s[..len].to_owned(),
+                        },
+                        len,
+                    ));
+                }
+                None => return Err(unterminated()),
+                _ => {}
+            }
+            first = false;
+
+            let start = next(&mut chars).ok_or_else(unterminated)?;
+            let mut lookahead = chars.clone();
+            let end = match (lookahead.next(), lookahead.peek()) {
+                (Some((_, '-')), Some(&(_, c))) if c != ']' => {
+                    chars.next();
+                    let end = next(&mut chars).ok_or_else(unterminated)?;
+                    if end < start {
+                        bail!("invalid range `{}-{}` in character class", start, end);
+                    }
+                    end
+                }
+                _ => start,
+            };
+            ranges.push((start, end));
+        }
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars.next().is_some_and(|c| c == '_' || c.is_alphabetic())
+        && chars.all(|c| c == '_' || c.is_alphanumeric())
+}
+
+/// Parses the quoted separator of a repetition at the start of `s`, returning
+/// it and its length in bytes including the quotes.
+fn parse_separator(s: &str) -> Result<(String, usize), Error> {
+    if !s.starts_with('"') {
+        bail!("expected a quoted separator after '*', found `{}`", s);
+    }
+    let len = quoted_len(s).ok_or_else(|| Error::new(format!("unterminated separator `{}`", s)))?;
+    let separator = unescape(&s[1..len - 1])
+        .ok_or_else(|| Error::new(format!("invalid separator `{}`", &s[..len])))?;
+    if separator.is_empty() {
+        bail!("the separator of a repetition must not be empty");
+    }
+    Ok((separator, len))
+}
+
+/// Replaces the escapes in `s` with the characters they stand for, like in a
+/// Rust string literal. Returns `None` if an escape is invalid.
+fn unescape(s: &str) -> Option<String> {
+    let mut unescaped = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            c @ ('\\' | '"' | '\'') => c,
+            // an ASCII character, like `\x2c`.
+            'x' => {
+                let high = chars.next()?.to_digit(8)?;
+                let low = chars.next()?.to_digit(16)?;
+                char::from((high * 16 + low) as u8)
+            }
+            // a character by its code point, like `\u{2c}`.
+            'u' => {
+                if chars.next()? != '{' {
+                    return None;
+                }
+                let mut code = 0;
+                let mut digits = 0;
+                loop {
+                    match chars.next()? {
+                        '}' if digits > 0 => break,
+                        '_' if digits > 0 => {}
+                        c if digits < 6 => {
+                            code = code * 16 + c.to_digit(16)?;
+                            digits += 1;
+                        }
+                        _ => return None,
+                    }
+                }
+                char::from_u32(code)?
+            }
+            // a line continuation skips the whitespace at the start of the
+            // next line.
+            '\n' => {
+                while chars
+                    .next_if(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+                    .is_some()
+                {}
+                continue;
+            }
+            _ => return None,
+        });
+    }
+    Some(unescaped)
+}
+
+/// Finds the length in bytes of the quoted string at the start of `s`, in
+/// which a `\` escapes the character after it.
+fn quoted_len(s: &str) -> Option<usize> {
+    let mut chars = s.char_indices().skip(1);
+    while let Some((n, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' => return Some(n + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits `s` into runs of whitespace and of other characters, returning each
+/// run and whether it is whitespace.
+pub fn split_whitespace_runs(s: &str) -> impl Iterator<Item = (&str, bool)> {
+    let mut rest = s;
+    core::iter::from_fn(move || {
+        let is_ws = rest.starts_with(char::is_whitespace);
+        let len = rest
+            .find(|c: char| c.is_whitespace() != is_ws)
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return None;
+        }
+        let (run, next) = rest.split_at(len);
+        rest = next;
+        Some((run, is_ws))
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,191 @@
+use alloc::borrow::ToOwned;
+use alloc::string::{String, ToString};
+use alloc::vec;
+
+use crate::{parse, Argument, CharClass, Format, Piece, Spec, Whitespace};
+
+/// Parses a format string that is a single placeholder.
+fn format(s: &str) -> Format {
+    match &parse(s).unwrap()[..] {
+        [Piece::Fmt(fmt)] => fmt.clone(),
+        pieces => panic!("expected a single placeholder, found {:?}", pieces),
+    }
+}
+
+fn error(s: &str) -> String {
+    parse(s).unwrap_err().to_string()
+}
+
+fn lit(s: &str) -> Piece {
+    Piece::Lit(s.to_owned(), Whitespace::Exact)
+}
+
+#[test]
+fn placeholders() {
+    assert_eq!(Argument::Implicit, format("{}").argument);
+    assert_eq!(Argument::Named("x".to_owned()), format("{x}").argument);
+    assert_eq!(Argument::Index(1), format("{1}").argument);
+    assert_eq!(Argument::Discard, format("{_}").argument);
+
+    let fmt = format("{ port : u16 }");
+    assert_eq!(Argument::Named("port".to_owned()), fmt.argument);
+    assert_eq!((Spec::Default, Some("u16")), (fmt.spec, fmt.ty.as_deref()));
+    assert_eq!("{ port : u16 }", fmt.source);
+
+    assert_eq!(
+        vec![lit("a: "), Piece::Fmt(format("{a}")), lit("!")],
+        parse("a: {a}!").unwrap()
+    );
+}
+
+#[test]
+fn specs() {
+    let specs = [
+        ("{:x}", Spec::LowerHex),
+        ("{:X}", Spec::UpperHex),
+        ("{:o}", Spec::Octal),
+        ("{:b}", Spec::Binary),
+    ];
+    for (s, spec) in specs {
+        let fmt = format(s);
+        assert_eq!((spec, None), (fmt.spec, fmt.ty));
+    }
+    let fmt = format("{:x as u8}");
+    assert_eq!((Spec::LowerHex, Some("u8")), (fmt.spec, fmt.ty.as_deref()));
+
+    let fmt = format("{:[a-z_] as String}");
+    let class = CharClass {
+        negated: false,
+        ranges: vec![('a', 'z'), ('_', '_')],
+        source: "[a-z_]".to_owned(),
+    };
+    assert_eq!(
+        (Spec::Class(class), Some("String")),
+        (fmt.spec, fmt.ty.as_deref())
+    );
+
+    // `]` right after the bracket, escapes and braces are part of the class.
+    let Spec::Class(class) = format(r"{:[^]\-{}]}").spec else {
+        panic!("expected a class");
+    };
+    assert!(class.negated);
+    assert_eq!(
+        vec![(']', ']'), ('-', '-'), ('{', '{'), ('}', '}')],
+        class.ranges
+    );
+}
+
+#[test]
+fn width() {
+    assert_eq!(Some(4), format("{:4}").width);
+    let fmt = format("{:2x as u8}");
+    assert_eq!((Some(2), Spec::LowerHex), (fmt.width, fmt.spec));
+    let fmt = format("{:3[a-z]}");
+    assert_eq!(Some(3), fmt.width);
+    assert!(matches!(fmt.spec, Spec::Class(_)));
+    assert_eq!(None, format("{}").width);
+}
+
+#[test]
+fn separators() {
+    assert_eq!(Some(", "), format(r#"{:*", "}"#).separator.as_deref());
+    let fmt = format(r#"{nums:*" " 2x as u8}"#);
+    assert_eq!(Some(" "), fmt.separator.as_deref());
+    assert_eq!((Some(2), Spec::LowerHex), (fmt.width, fmt.spec));
+    // braces in separators do not end the placeholder.
+    assert_eq!(Some("}{"), format(r#"{:*"}{"}"#).separator.as_deref());
+
+    // separators have the escapes of string literals.
+    let fmt = format(r#"{:*"\x2c\u{2c}\u{1_F600}\n\r\t\\\"\'\0"}"#);
+    assert_eq!(Some(",,\u{1F600}\n\r\t\\\"'\0"), fmt.separator.as_deref());
+    let fmt = format("{:*\"a\\\n    b\"}");
+    assert_eq!(Some("ab"), fmt.separator.as_deref());
+}
+
+#[test]
+fn groups() {
+    let optional = |s| Format {
+        optional: true,
+        ..format(s)
+    };
+    assert_eq!(
+        vec![
+            Piece::Fmt(format("{}")),
+            Piece::OptionalStart,
+            lit(" ("),
+            Piece::Fmt(optional("{}")),
+            Piece::OptionalStart,
+            lit("/"),
+            Piece::Fmt(optional("{}")),
+            Piece::OptionalEnd,
+            lit(")"),
+            Piece::OptionalEnd,
+        ],
+        parse("{}[ ({}[/{}])]").unwrap()
+    );
+
+    // doubled brackets are literal.
+    assert_eq!(
+        vec![lit("["), Piece::Fmt(format("{}")), lit("]")],
+        parse("[[{}]]").unwrap()
+    );
+}
+
+#[test]
+fn braces_and_directives() {
+    assert_eq!(vec![lit("{}")], parse("{{}}").unwrap());
+    assert_eq!(
+        vec![lit("{ "), Piece::Fmt(format("{}")), lit(" }")],
+        parse("{{ {} }}").unwrap()
+    );
+
+    assert_eq!(
+        vec![
+            lit("a "),
+            Piece::Lit(" b ".to_owned(), Whitespace::Flexible(0)),
+            Piece::Fmt(format("{}")),
+            Piece::Lit(" c".to_owned(), Whitespace::Flexible(1)),
+        ],
+        parse("a {!ws} b {}{!ws+} c").unwrap()
+    );
+}
+
+#[test]
+fn errors() {
+    let errors = [
+        ("}", "mismatched '}' with no opening braces, index=0"),
+        ("a{", "mismatched '{' for index=1"),
+        ("{{}", "mismatched '}' with no opening braces, index=2"),
+        ("[{}", "mismatched '[' for index=0"),
+        ("{}]", "mismatched ']' with no opening bracket, index=2"),
+        ("[]", "empty optional group, index=1"),
+        (
+            "[INFO] {}",
+            "optional group `[INFO]` has no placeholders, use `[[` and `]]` for literal brackets",
+        ),
+        ("{!nope}", "unknown directive `!nope`"),
+        ("{a b}", "expected an argument name or index, found `a b`"),
+        ("{:0}", "invalid width `0`"),
+        (
+            "{:x u8}",
+            "expected `as` and a type after the radix, found `u8`",
+        ),
+        (
+            "{:[a-z] u8}",
+            "expected `as` and a type after the class, found `u8`",
+        ),
+        ("{:[z-a]}", "invalid range `z-a` in character class"),
+        ("{:[a}", "unterminated character class `[a`"),
+        ("{:*,}", "expected a quoted separator after '*', found `,`"),
+        (
+            r#"{:*""}"#,
+            "the separator of a repetition must not be empty",
+        ),
+        (r#"{:*"\q"}"#, r#"invalid separator `"\q"`"#),
+        (r#"{:*"\x80"}"#, r#"invalid separator `"\x80"`"#),
+        (r#"{:*"\u{D800}"}"#, r#"invalid separator `"\u{D800}"`"#),
+    ];
+    for (s, message) in errors {
+        assert_eq!(message, error(s), "{}", s);
+    }
+}
@@ -0,0 +1,147 @@
+//! The async equivalents of [`Scanner`] and [`Lines`], for the `AsyncBufRead`
+//! traits of `futures` and `tokio`, which have the same methods.
+//!
+//! [`Scanner`]: crate::Scanner
+//! [`Lines`]: crate::Lines
+
+macro_rules! async_io {
+    ($AsyncBufRead:path) => {
+        use std::future::poll_fn;
+        use std::pin::Pin;
+        use std::task::Poll;
+
+        use $AsyncBufRead as AsyncBufRead;
+
+        use crate::stream::{Next, Records};
+        use crate::{Pattern, ScanError, Values};
+
+        /// Splits the input of an async reader into records ending with a
+        /// terminator, like [`Scanner`](crate::Scanner).
+        pub struct Scanner<R> {
+            reader: R,
+            records: Records,
+        }
+
+        /// Scans the lines of an async reader, like [`Lines`](crate::Lines).
+        /// Created by [`lines`].
+        pub struct Lines<R, F> {
+            scanner: Scanner<R>,
+            scan: F,
+        }
+
+        impl<R: AsyncBufRead + Unpin> Scanner<R> {
+            /// Creates a scanner of the records of `reader` that end with
+            /// `terminator`. The last record may end with the input instead.
+            ///
+            /// # Panics
+            ///
+            /// Panics if `terminator` is empty.
+            pub fn new(reader: R, terminator: &str) -> Self {
+                Self {
+                    reader,
+                    records: Records::new(terminator),
+                }
+            }
+
+            /// Reads the next record, without its terminator. Returns `None`
+            /// at the end of the input.
+            pub async fn next_record(&mut self) -> Option<Result<&str, ScanError>> {
+                loop {
+                    match self.records.next() {
+                        Next::Record(end, next) => return Some(self.records.take(end, next)),
+                        Next::End => return None,
+                        Next::Fill => {
+                            if let Err(e) = self.fill().await {
+                                return Some(Err(e.into()));
+                            }
+                        }
+                    }
+                }
+            }
+
+            /// Reads the next record and scans the whole of it with `pattern`.
+            /// Returns `None` at the end of the input.
+            pub async fn scan<T: Values>(
+                &mut self,
+                pattern: &Pattern<T>,
+            ) -> Option<Result<T, ScanError>> {
+                Some(
+                    self.next_record()
+                        .await?
+                        .and_then(|record| pattern.scan(record)),
+                )
+            }
+
+            /// The number of records read so far.
+            pub fn records(&self) -> usize {
+                self.records.count()
+            }
+
+            /// Returns the reader, dropping the input that was read but not
+            /// consumed.
+            pub fn into_inner(self) -> R {
+                self.reader
+            }
+
+            /// Copies what the reader has buffered into the records.
+            async fn fill(&mut self) -> std::io::Result<()> {
+                poll_fn(|cx| {
+                    let available = match Pin::new(&mut self.reader).poll_fill_buf(cx) {
+                        Poll::Ready(Ok(available)) => available,
+                        Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                        Poll::Pending => return Poll::Pending,
+                    };
+                    let spare = self.records.spare();
+                    let n = available.len().min(spare.len());
+                    spare[..n].copy_from_slice(&available[..n]);
+                    self.records.fill(n);
+                    Pin::new(&mut self.reader).consume(n);
+                    Poll::Ready(Ok(()))
+                })
+                .await
+            }
+        }
+
+        /// Scans each line of `reader` with `scan`, which is given the line
+        /// without its line terminator, like [`scan_lines!`].
+        ///
+        /// `scan` can be a closure calling [`scan!`] or [`Pattern::scan`].
+        ///
+        /// [`scan_lines!`]: crate::scan_lines
+        /// [`scan!`]: crate::scan
+        pub fn lines<T, R, F>(reader: R, scan: F) -> Lines<R, F>
+        where
+            R: AsyncBufRead + Unpin,
+            F: FnMut(&str) -> Result<T, ScanError>,
+        {
+            Lines {
+                scanner: Scanner::new(reader, "\n"),
+                scan,
+            }
+        }
+
+        impl<T, R, F> Lines<R, F>
+        where
+            R: AsyncBufRead + Unpin,
+            F: FnMut(&str) -> Result<T, ScanError>,
+        {
+            /// Reads and scans the next line. Returns `None` at the end of the
+            /// input.
+            pub async fn next_line(&mut self) -> Option<Result<T, ScanError>> {
+                let line = self.scanner.records() + 1;
+                let result =
+                    self.scanner.next_record().await?.and_then(|record| {
+                        (self.scan)(record.strip_suffix('\r').unwrap_or(record))
+                    });
+                Some(result.map_err(|e| e.with_line(line)))
+            }
+
+            /// The number of lines read so far.
+            pub fn line(&self) -> usize {
+                self.scanner.records()
+            }
+        }
+    };
+}
+
+pub(crate) use async_io;
@@ -0,0 +1,337 @@
+//! Patterns whose placeholders declare the types of their values, which are
+//! scanned into [`Value`]s.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::io::BufRead;
+
+use scanfmt_syntax::{self as syntax, Argument, Spec};
+
+use crate::matcher::{self, Instr, Slots};
+use crate::pattern::{compile, formats, kind, Kind, PatternError};
+#[cfg(feature = "std")]
+use crate::Lines;
+use crate::{Scan, ScanBinary, ScanError, ScanLowerHex, ScanOctal, ScanUpperHex};
+
+/// A format string that is compiled at runtime, whose placeholders declare
+/// the types of their values, like `{port:u16}`.
+///
+/// The types are the ones that implement [`Scan`] in this crate: the integer
+/// and float types, and `str` (or `String`) for text. Placeholders without a
+/// type scan text, and integers can be scanned in a radix, like
+/// `{flags:x as u32}`. Discarded placeholders like `{_:u8}` are checked, but
+/// have no value. Values in optional groups are [`Value::Option`]s, and
+/// repetitions are [`Value::Vec`]s.
+///
+/// ```
+/// use scanfmt::{DynPattern, Value};
+///
+/// let pattern = DynPattern::new("{host:str}:{port:u16}")?;
+/// let values = pattern.scan("localhost:8080")?;
+/// assert_eq!(
+///     vec![Value::Str("localhost".to_owned()), Value::U16(8080)],
+///     values
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct DynPattern {
+    program: Vec<Instr>,
+    /// The placeholder of each slot of the program.
+    fields: Vec<Field>,
+    /// The names of the values, in order.
+    names: Vec<Option<String>>,
+}
+
+#[derive(Debug)]
+struct Field {
+    /// The index of the value, or `None` if it is discarded.
+    value: Option<usize>,
+    ty: Type,
+    radix: Radix,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Radix {
+    Decimal,
+    Binary,
+    Octal,
+    LowerHex,
+    UpperHex,
+}
+
+macro_rules! types {
+    (
+        $(int $ivariant:ident $int:ident $iname:literal,)*
+        $(float $fvariant:ident $float:ident $fname:literal,)*
+    ) => {
+        /// A value scanned by a [`DynPattern`].
+        #[non_exhaustive]
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum Value {
+            Str(String),
+            $($ivariant($int),)*
+            $($fvariant($float),)*
+            /// The value of a placeholder in an optional group.
+            Option(Option<Box<Value>>),
+            /// The values of a repetition.
+            Vec(Vec<Value>),
+        }
+
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        enum Type {
+            Str,
+            $($ivariant,)*
+            $($fvariant,)*
+        }
+
+        impl Type {
+            fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    "str" | "String" => Some(Self::Str),
+                    $($iname => Some(Self::$ivariant),)*
+                    $($fname => Some(Self::$fvariant),)*
+                    _ => None,
+                }
+            }
+
+            fn is_integer(self) -> bool {
+                matches!(self, $(Self::$ivariant)|*)
+            }
+
+            fn is_valid_start(self, radix: Radix, c: char) -> bool {
+                match (self, radix) {
+                    (Self::Str, _) => <String as Scan>::is_valid_start(c),
+                    $(
+                        (Self::$ivariant, Radix::Decimal) => <$int as Scan>::is_valid_start(c),
+                        (Self::$ivariant, Radix::Binary) => <$int as ScanBinary>::is_valid_start(c),
+                        (Self::$ivariant, Radix::Octal) => <$int as ScanOctal>::is_valid_start(c),
+                        (Self::$ivariant, Radix::LowerHex) => {
+                            <$int as ScanLowerHex>::is_valid_start(c)
+                        }
+                        (Self::$ivariant, Radix::UpperHex) => {
+                            <$int as ScanUpperHex>::is_valid_start(c)
+                        }
+                    )*
+                    $((Self::$fvariant, _) => <$float as Scan>::is_valid_start(c),)*
+                }
+            }
+
+            fn prefers_longest(self) -> bool {
+                match self {
+                    Self::Str => <String as Scan>::prefers_longest(),
+                    $(Self::$ivariant => <$int as Scan>::prefers_longest(),)*
+                    $(Self::$fvariant => <$float as Scan>::prefers_longest(),)*
+                }
+            }
+
+            fn scan(self, radix: Radix, s: &str) -> Result<Value, ScanError> {
+                Ok(match (self, radix) {
+                    (Self::Str, _) => Value::Str(<String as Scan>::scan(s)?),
+                    $(
+                        (Self::$ivariant, Radix::Decimal) => {
+                            Value::$ivariant(<$int as Scan>::scan(s)?)
+                        }
+                        (Self::$ivariant, Radix::Binary) => {
+                            Value::$ivariant(<$int as ScanBinary>::scan(s)?)
+                        }
+                        (Self::$ivariant, Radix::Octal) => {
+                            Value::$ivariant(<$int as ScanOctal>::scan(s)?)
+                        }
+                        (Self::$ivariant, Radix::LowerHex) => {
+                            Value::$ivariant(<$int as ScanLowerHex>::scan(s)?)
+                        }
+                        (Self::$ivariant, Radix::UpperHex) => {
+                            Value::$ivariant(<$int as ScanUpperHex>::scan(s)?)
+                        }
+                    )*
+                    $((Self::$fvariant, _) => Value::$fvariant(<$float as Scan>::scan(s)?),)*
+                })
+            }
+        }
+    };
+}
+
+types! {
+    int I8 i8 "i8",
+    int I16 i16 "i16",
+    int I32 i32 "i32",
+    int I64 i64 "i64",
+    int I128 i128 "i128",
+    int Isize isize "isize",
+    int U8 u8 "u8",
+    int U16 u16 "u16",
+    int U32 u32 "u32",
+    int U64 u64 "u64",
+    int U128 u128 "u128",
+    int Usize usize "usize",
+    float F32 f32 "f32",
+    float F64 f64 "f64",
+}
+
+impl DynPattern {
+    /// Compiles the format string `fmt`.
+    pub fn new(fmt: &str) -> Result<Self, PatternError> {
+        let pieces = syntax::parse(fmt)?;
+        let mut fields = Vec::new();
+        let mut names = Vec::<Option<String>>::new();
+        for fmt in formats(&pieces) {
+            let name = match &fmt.argument {
+                Argument::Implicit | Argument::Discard => None,
+                Argument::Named(name) => {
+                    if names.iter().flatten().any(|other| other == name) {
+                        return Err(PatternError::new(format!(
+                            "`{}` has the same name as an earlier placeholder",
+                            fmt.source
+                        )));
+                    }
+                    Some(name.clone())
+                }
+                Argument::Index(_) => {
+                    return Err(PatternError::new(format!(
+                        "`{}` refers to an argument, but patterns have no arguments",
+                        fmt.source
+                    )))
+                }
+            };
+
+            let radix = match fmt.spec {
+                Spec::Default | Spec::Class(_) => Radix::Decimal,
+                Spec::Binary => Radix::Binary,
+                Spec::Octal => Radix::Octal,
+                Spec::LowerHex => Radix::LowerHex,
+                Spec::UpperHex => Radix::UpperHex,
+            };
+            let ty = match &fmt.ty {
+                Some(ty) => Type::from_name(ty).ok_or_else(|| {
+                    PatternError::new(format!("`{}` has an unknown type `{}`", fmt.source, ty))
+                })?,
+                None if radix == Radix::Decimal => Type::Str,
+                None => {
+                    return Err(PatternError::new(format!(
+                        "`{}` has a radix, but no integer type, like `{{:x as u32}}`",
+                        fmt.source
+                    )))
+                }
+            };
+            if radix != Radix::Decimal && !ty.is_integer() {
+                return Err(PatternError::new(format!(
+                    "`{}` has a radix, but its type is not an integer",
+                    fmt.source
+                )));
+            }
+
+            let value = match fmt.argument {
+                Argument::Discard => None,
+                _ => {
+                    names.push(name);
+                    Some(names.len() - 1)
+                }
+            };
+            fields.push(Field {
+                value,
+                ty,
+                radix,
+                kind: kind(fmt),
+            });
+        }
+
+        Ok(Self {
+            program: compile(&pieces),
+            fields,
+            names,
+        })
+    }
+
+    /// Scans the whole of `input` into the values of the placeholders that are
+    /// not discarded, in order.
+    pub fn scan(&self, input: &str) -> Result<Vec<Value>, ScanError> {
+        let mut slots = DynSlots {
+            fields: &self.fields,
+            values: vec![Vec::new(); self.names.len()],
+        };
+        matcher::run(&self.program, input, &mut slots)?;
+
+        let mut values = slots.values;
+        Ok(self
+            .fields
+            .iter()
+            .filter_map(|field| Some((field.value?, field.kind)))
+            .map(|(value, kind)| {
+                let mut values = core::mem::take(&mut values[value]);
+                match kind {
+                    Kind::Single => values.pop().unwrap(),
+                    Kind::Optional => Value::Option(values.pop().map(Box::new)),
+                    Kind::Repeated => Value::Vec(values),
+                }
+            })
+            .collect())
+    }
+
+    /// Scans each line of `reader`, without its line terminator.
+    #[cfg(feature = "std")]
+    pub fn lines<R: BufRead>(
+        &self,
+        reader: R,
+    ) -> Lines<R, impl FnMut(&str) -> Result<Vec<Value>, ScanError> + '_> {
+        Lines::new(reader, move |line: &str| self.scan(line))
+    }
+
+    /// Scans the whole of `input` into the values of the named placeholders,
+    /// by name.
+    #[cfg(feature = "std")]
+    pub fn scan_map(&self, input: &str) -> Result<HashMap<String, Value>, ScanError> {
+        let values = self.scan(input)?;
+        Ok(self
+            .names
+            .iter()
+            .zip(values)
+            .filter_map(|(name, value)| Some((name.clone()?, value)))
+            .collect())
+    }
+
+    /// The names of the values, in order, or `None` for placeholders without
+    /// a name.
+    pub fn names(&self) -> impl Iterator<Item = Option<&str>> {
+        self.names.iter().map(Option::as_deref)
+    }
+}
+
+struct DynSlots<'a> {
+    fields: &'a [Field],
+    /// The values scanned for each placeholder so far.
+    values: Vec<Vec<Value>>,
+}
+
+impl Slots<'_> for DynSlots<'_> {
+    fn is_valid_start(&self, slot: usize, c: char) -> bool {
+        let field = &self.fields[slot];
+        field.ty.is_valid_start(field.radix, c)
+    }
+
+    fn prefers_longest(&self, slot: usize) -> bool {
+        self.fields[slot].ty.prefers_longest()
+    }
+
+    fn scan(&mut self, slot: usize, s: &str) -> Result<(), ScanError> {
+        let field = &self.fields[slot];
+        let value = field.ty.scan(field.radix, s)?;
+        if let Some(idx) = field.value {
+            self.values[idx].push(value);
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, slot: usize) {
+        if let Some(idx) = self.fields[slot].value {
+            self.values[idx].pop();
+        }
+    }
+}
@@ -0,0 +1,6 @@
+//! Async scanning of [`futures::io::AsyncBufRead`]s, which is enabled by the
+//! `futures-io` feature.
+//!
+//! [`futures::io::AsyncBufRead`]: ::futures_io::AsyncBufRead
+
+crate::async_io::async_io!(::futures_io::AsyncBufRead);
@@ -1,4 +1,8 @@
-use crate::{Scan, ScanBinary, ScanError, ScanLowerHex, ScanOctal, ScanUpperHex};
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::String;
+
+use crate::pattern::{Radix, RadixScan};
+use crate::{Scan, ScanBinary, ScanBorrowed, ScanError, ScanLowerHex, ScanOctal, ScanUpperHex};
 
 impl Scan for String {
     fn scan(s: &str) -> Result<Self, ScanError> {
@@ -8,24 +12,74 @@ impl Scan for String {
     fn is_valid_start(_: char) -> bool {
         true
     }
+
+    fn prefers_longest() -> bool {
+        false
+    }
+}
+
+impl<'a, T: Scan> ScanBorrowed<'a> for T {
+    fn is_valid_start(c: char) -> bool {
+        T::is_valid_start(c)
+    }
+
+    fn scan(s: &'a str) -> Result<Self, ScanError> {
+        T::scan(s)
+    }
+
+    fn prefers_longest() -> bool {
+        T::prefers_longest()
+    }
+}
+
+impl<'a> ScanBorrowed<'a> for &'a str {
+    fn is_valid_start(_: char) -> bool {
+        true
+    }
+
+    fn scan(s: &'a str) -> Result<Self, ScanError> {
+        Ok(s)
+    }
+
+    fn prefers_longest() -> bool {
+        false
+    }
+}
+
+impl<'a> ScanBorrowed<'a> for Cow<'a, str> {
+    fn is_valid_start(_: char) -> bool {
+        true
+    }
+
+    fn scan(s: &'a str) -> Result<Self, ScanError> {
+        Ok(Cow::Borrowed(s))
+    }
+
+    fn prefers_longest() -> bool {
+        false
+    }
 }
 
 macro_rules! int_impl {
     ($($intTy:ident)+) => {$(
         impl Scan for $intTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                s.parse().map_err(|e| ScanError::Custom(Box::new(e)))
+                s.parse().map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
             fn is_valid_start(c: char) -> bool {
                 c.is_digit(10) || c == '-' || c == '+'
             }
+
+            fn radix(radix: Radix) -> Option<RadixScan<Self>> {
+                Some(RadixScan::new(radix))
+            }
         }
 
         impl ScanBinary for $intTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                <$intTy>::from_str_radix(s, 2).map_err(|e| ScanError::Custom(Box::new(e)))
+                <$intTy>::from_str_radix(s, 2).map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
@@ -36,7 +90,7 @@ macro_rules! int_impl {
 
         impl ScanOctal for $intTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                <$intTy>::from_str_radix(s, 8).map_err(|e| ScanError::Custom(Box::new(e)))
+                <$intTy>::from_str_radix(s, 8).map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
@@ -47,7 +101,7 @@ macro_rules! int_impl {
 
         impl ScanLowerHex for $intTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                <$intTy>::from_str_radix(s, 16).map_err(|e| ScanError::Custom(Box::new(e)))
+                <$intTy>::from_str_radix(s, 16).map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
@@ -58,7 +112,7 @@ macro_rules! int_impl {
 
         impl ScanUpperHex for $intTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                <$intTy>::from_str_radix(&s.to_ascii_lowercase(), 16).map_err(|e| ScanError::Custom(Box::new(e)))
+                <$intTy>::from_str_radix(&s.to_ascii_lowercase(), 16).map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
@@ -73,18 +127,22 @@ macro_rules! uint_impl {
     ($($uintTy:ident)+) => {$(
         impl Scan for $uintTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                s.parse().map_err(|e| ScanError::Custom(Box::new(e)))
+                s.parse().map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
             fn is_valid_start(c: char) -> bool {
                 c.is_digit(10) || c == '+'
             }
+
+            fn radix(radix: Radix) -> Option<RadixScan<Self>> {
+                Some(RadixScan::new(radix))
+            }
         }
 
         impl ScanBinary for $uintTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                <$uintTy>::from_str_radix(s, 2).map_err(|e| ScanError::Custom(Box::new(e)))
+                <$uintTy>::from_str_radix(s, 2).map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
@@ -95,7 +153,7 @@ macro_rules! uint_impl {
 
         impl ScanOctal for $uintTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                <$uintTy>::from_str_radix(s, 8).map_err(|e| ScanError::Custom(Box::new(e)))
+                <$uintTy>::from_str_radix(s, 8).map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
@@ -106,7 +164,7 @@ macro_rules! uint_impl {
 
         impl ScanLowerHex for $uintTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                <$uintTy>::from_str_radix(s, 16).map_err(|e| ScanError::Custom(Box::new(e)))
+                <$uintTy>::from_str_radix(s, 16).map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
@@ -117,7 +175,7 @@ macro_rules! uint_impl {
 
         impl ScanUpperHex for $uintTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                <$uintTy>::from_str_radix(&s.to_ascii_lowercase(), 16).map_err(|e| ScanError::Custom(Box::new(e)))
+                <$uintTy>::from_str_radix(&s.to_ascii_lowercase(), 16).map_err(|e| ScanError::custom(e))
             }
 
             // leading sign or a number is a valid start.
@@ -135,7 +193,7 @@ macro_rules! float_impl {
     ($($floatTy:ident)+) => {$(
         impl Scan for $floatTy {
             fn scan(s: &str) -> Result<Self, ScanError> {
-                s.parse().map_err(|e| ScanError::Custom(Box::new(e)))
+                s.parse().map_err(|e| ScanError::custom(e))
             }
 
             // number, inf, NaN
@@ -1,30 +1,129 @@
-use std::{error::Error, fmt::Display};
+#![cfg_attr(not(feature = "std"), no_std)]
 
-pub use scanfmt_macros::scanfmt;
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::error::Error;
+use core::fmt::Display;
+#[cfg(feature = "std")]
+use std::io;
+
+pub use dynamic::{DynPattern, Value};
+pub use pattern::{Capture, Pattern, PatternError, Values};
+use pattern::{Radix, RadixScan};
+#[cfg(feature = "std")]
+pub use read::Lines;
+pub use scanfmt_macros::{scan, scanfmt, scanfmt_prefix, Scan};
+#[cfg(feature = "std")]
+pub use scanfmt_macros::{scan_lines, scan_read, scanln};
+#[cfg(feature = "std")]
+pub use stream::Scanner;
 
 #[cfg(test)]
 extern crate self as scanfmt;
 
+#[cfg(any(feature = "futures-io", feature = "tokio"))]
+mod async_io;
+mod dynamic;
+#[cfg(feature = "futures-io")]
+pub mod futures;
 mod impl_;
 pub mod macro_support;
+mod matcher;
+mod pattern;
+#[cfg(feature = "std")]
+mod read;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "tokio")]
+pub mod tokio;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests;
 
+/// An error that occurred while scanning.
+///
+/// Errors returned from [`scanfmt!`] are located: they carry the byte offset
+/// into the input, the piece of the format string that was being processed and,
+/// where applicable, the literal text that was expected.
+#[derive(Debug)]
+pub struct ScanError {
+    kind: ScanErrorKind,
+    offset: Option<usize>,
+    piece: Option<Piece>,
+    expected: Option<Cow<'static, str>>,
+    line: Option<usize>,
+}
+
+/// The kind of a [`ScanError`].
 #[non_exhaustive]
 #[derive(Debug)]
-pub enum ScanError {
+pub enum ScanErrorKind {
     LiteralMismatch,
     LiteralNotFound,
+    /// No characters matched a character class.
+    ClassMismatch,
     Eof,
+    /// The pattern matched, but was followed by the contained text.
+    TrailingInput(String),
+    /// Reading the input failed.
+    #[cfg(feature = "std")]
+    Io(io::Error),
     Custom(Box<dyn Error + Send + Sync>),
 }
 
+/// A piece of a format string: either literal text or a placeholder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piece {
+    index: usize,
+    source: Cow<'static, str>,
+}
+
 /// A trait for something that can be scanned.
 pub trait Scan: Sized {
     /// Test if the given character is a valid start for the item to scan.
     fn is_valid_start(c: char) -> bool;
     fn scan(s: &str) -> Result<Self, ScanError>;
+
+    /// When this item is not delimited by a literal, such as when it is
+    /// directly followed by another placeholder, whether the longest candidate
+    /// should be tried first. Defaults to `true`.
+    ///
+    /// Items that can contain anything, like `String`, should return `false`
+    /// so that the following placeholder gets as much of the input as it can
+    /// scan.
+    fn prefers_longest() -> bool {
+        true
+    }
+
+    /// How to scan this item in `radix`, if it can be, for the placeholders
+    /// with a radix of [`Pattern`]s. The integers scan with their
+    /// [`ScanBinary`], [`ScanOctal`], [`ScanLowerHex`] and [`ScanUpperHex`]
+    /// implementations.
+    #[doc(hidden)]
+    fn radix(radix: Radix) -> Option<RadixScan<Self>> {
+        let _ = radix;
+        None
+    }
+}
+
+/// A trait for something that can be scanned from input it may borrow from,
+/// like `&'a str`, which the default placeholders of the macros scan with.
+///
+/// Every type that implements [`Scan`] implements it. Implement it instead of
+/// [`Scan`] for types that borrow from the input, so that scanning them does
+/// not allocate.
+pub trait ScanBorrowed<'a>: Sized {
+    /// Test if the given character is a valid start for the item to scan.
+    fn is_valid_start(c: char) -> bool;
+    fn scan(s: &'a str) -> Result<Self, ScanError>;
+
+    /// Like [`Scan::prefers_longest`].
+    fn prefers_longest() -> bool {
+        true
+    }
 }
 
 /// A trait for something that can be scanned in octal format.
@@ -49,23 +148,167 @@ pub trait ScanUpperHex: Sized {
     fn scan(s: &str) -> Result<Self, ScanError>;
 }
 
-impl Display for ScanError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl ScanError {
+    /// Creates an error of the given kind that is not yet located in the input.
+    pub fn new(kind: ScanErrorKind) -> Self {
+        Self {
+            kind,
+            offset: None,
+            piece: None,
+            expected: None,
+            line: None,
+        }
+    }
+
+    /// Creates a custom error, typically from a [`Scan`] implementation.
+    pub fn custom<E: Into<Box<dyn Error + Send + Sync>>>(error: E) -> Self {
+        Self::new(ScanErrorKind::Custom(error.into()))
+    }
+
+    pub fn kind(&self) -> &ScanErrorKind {
+        &self.kind
+    }
+
+    pub fn into_kind(self) -> ScanErrorKind {
+        self.kind
+    }
+
+    /// The byte offset into the input at which the error occurred.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// The piece of the format string that was being processed.
+    pub fn piece(&self) -> Option<&Piece> {
+        self.piece.as_ref()
+    }
+
+    /// The literal text that was expected, if any.
+    pub fn expected(&self) -> Option<&str> {
+        self.expected.as_deref()
+    }
+
+    /// The number of the line of the input, starting from 1, if the input was
+    /// scanned line by line.
+    pub fn line(&self) -> Option<usize> {
+        self.line
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn with_line(mut self, line: usize) -> Self {
+        self.line = Some(line);
+        self
+    }
+}
+
+impl Piece {
+    pub const fn new(index: usize, source: &'static str) -> Self {
+        Self {
+            index,
+            source: Cow::Borrowed(source),
+        }
+    }
+
+    /// Like [`Piece::new`], for pieces of patterns that are not known at
+    /// compile time.
+    pub(crate) fn owned(index: usize, source: String) -> Self {
+        Self {
+            index,
+            source: Cow::Owned(source),
+        }
+    }
+
+    /// The index of this piece in the format string.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The source text of this piece: the literal text, or the placeholder
+    /// including its braces, e.g. `{name:x}`.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+impl From<ScanErrorKind> for ScanError {
+    fn from(kind: ScanErrorKind) -> Self {
+        Self::new(kind)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for ScanError {
+    fn from(e: io::Error) -> Self {
+        Self::new(ScanErrorKind::Io(e))
+    }
+}
+
+impl Display for ScanErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             Self::Eof => f.write_str("reached end of sequence while parsing"),
             Self::LiteralMismatch => f.write_str("literal mismatch"),
             Self::LiteralNotFound => f.write_str("literal was not found"),
+            Self::ClassMismatch => f.write_str("no characters matched the class"),
+            Self::TrailingInput(rest) => write!(f, "unexpected trailing input {:?}", rest),
+            #[cfg(feature = "std")]
+            Self::Io(e) => e.fmt(f),
             Self::Custom(c) => c.fmt(f),
         }
     }
 }
 
+impl Display for ScanError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match (&self.kind, self.expected()) {
+            (ScanErrorKind::LiteralMismatch | ScanErrorKind::LiteralNotFound, Some(expected)) => {
+                write!(f, "expected {:?}", expected)?
+            }
+            (ScanErrorKind::ClassMismatch, Some(class)) => {
+                write!(f, "expected a character in {}", class)?
+            }
+            (kind, _) => kind.fmt(f)?,
+        }
+        if let Some(line) = self.line {
+            write!(f, " on line {}", line)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
+        }
+        if let Some(piece) = &self.piece {
+            write!(f, " while scanning `{}`", piece.source)?;
+        }
+        Ok(())
+    }
+}
+
 impl Error for ScanError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
-        if let Self::Custom(c) = self {
-            Some(c.as_ref())
-        } else {
-            None
+        match &self.kind {
+            #[cfg(feature = "std")]
+            ScanErrorKind::Io(e) => Some(e),
+            ScanErrorKind::Custom(c) => Some(c.as_ref()),
+            _ => None,
         }
     }
 }
+
+/// Macro inputs that are rejected at compile time.
+///
+/// An argument cannot be passed twice:
+///
+/// ```compile_fail
+/// let mut a: u32;
+/// scanfmt::scanfmt!("1 2", "{} {}", a, a);
+/// # Ok::<(), scanfmt::ScanError>(())
+/// ```
+///
+/// Optional groups must contain a placeholder, so brackets around text alone
+/// must be doubled:
+///
+/// ```compile_fail
+/// let (msg,): (String,) = scanfmt::scan!("[INFO] hi", "[INFO] {}")?;
+/// # Ok::<(), scanfmt::ScanError>(())
+/// ```
+#[cfg(doctest)]
+pub struct CompileFail;
@@ -1,20 +1,111 @@
-pub use crate::ScanError;
-pub use crate::{Scan, ScanBinary, ScanLowerHex, ScanOctal, ScanUpperHex};
+#[cfg(feature = "std")]
+use std::io::BufRead;
 
-pub fn advance<'a>(source: &'a str, literal: &str) -> Result<&'a str, ScanError> {
-    if source.len() < literal.len() {
-        return Err(ScanError::LiteralNotFound);
+pub use alloc::borrow::Cow;
+pub use alloc::vec::Vec;
+
+pub use crate::matcher::{is_valid_start, run, CharClass, Instr, Slots};
+pub use crate::pattern::{Radix, RadixScan};
+#[cfg(feature = "std")]
+pub use crate::Lines;
+pub use crate::{Piece, ScanError, ScanErrorKind};
+pub use crate::{Scan, ScanBinary, ScanBorrowed, ScanLowerHex, ScanOctal, ScanUpperHex};
+
+/// The type of values of untyped `{_}` placeholders, which are dropped.
+///
+/// It accepts anything, or any number in the radix of the placeholder.
+pub struct Discard;
+
+impl Scan for Discard {
+    fn is_valid_start(_: char) -> bool {
+        true
+    }
+
+    fn scan(_: &str) -> Result<Self, ScanError> {
+        Ok(Discard)
+    }
+
+    fn prefers_longest() -> bool {
+        false
     }
 
-    if source.is_char_boundary(literal.len()) {
-        let (eq_literal, rest) = source.split_at(literal.len());
+    fn radix(radix: Radix) -> Option<RadixScan<Self>> {
+        Some(RadixScan::new(radix))
+    }
+}
+
+macro_rules! discard_radix_impl {
+    ($($trait_:ident $radix:literal)+) => {$(
+        impl $trait_ for Discard {
+            fn is_valid_start(c: char) -> bool {
+                c.is_digit($radix) || c == '-' || c == '+'
+            }
+
+            fn scan(s: &str) -> Result<Self, ScanError> {
+                let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+                if !digits.is_empty() && digits.chars().all(|c| c.is_digit($radix)) {
+                    Ok(Discard)
+                } else {
+                    Err(ScanError::custom("invalid digit found in string"))
+                }
+            }
+        }
+    )+};
+}
+
+discard_radix_impl!(ScanBinary 2 ScanOctal 8 ScanLowerHex 16);
 
-        if eq_literal == literal {
-            Ok(rest)
+impl ScanUpperHex for Discard {
+    fn is_valid_start(c: char) -> bool {
+        c.is_ascii_digit() || matches!(c, 'A'..='F' | '-' | '+')
+    }
+
+    // like the integers, the digits are accepted in either case.
+    fn scan(s: &str) -> Result<Self, ScanError> {
+        let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+        if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            Ok(Discard)
         } else {
-            Err(ScanError::LiteralMismatch)
+            Err(ScanError::custom("invalid digit found in string"))
         }
+    }
+}
+
+/// Returns the error that got the furthest into the input, or `best` if both
+/// got as far.
+pub fn furthest(best: ScanError, e: ScanError) -> ScanError {
+    if best.offset >= e.offset {
+        best
     } else {
-        Err(ScanError::LiteralMismatch)
+        e
     }
 }
+
+/// Reads the line scanned by `scanln!` and `scan_read!`, without its line
+/// terminator. This is a method so that the reader is borrowed like the
+/// writer of `write!`.
+#[cfg(feature = "std")]
+pub trait ReadLine {
+    fn __scanfmt_read_line(&mut self) -> Result<String, ScanError>;
+}
+
+#[cfg(feature = "std")]
+impl<R: BufRead + ?Sized> ReadLine for R {
+    fn __scanfmt_read_line(&mut self) -> Result<String, ScanError> {
+        let mut line = String::new();
+        if !crate::read::read_line(self, &mut line)? {
+            return Err(ScanErrorKind::Eof.into());
+        }
+        Ok(line)
+    }
+}
+
+/// Creates the iterator of `scan_lines!`.
+#[cfg(feature = "std")]
+pub fn lines<T, R, F>(reader: R, scan: F) -> Lines<R, F>
+where
+    R: BufRead,
+    F: FnMut(&str) -> Result<T, ScanError>,
+{
+    Lines::new(reader, scan)
+}
@@ -0,0 +1,553 @@
+//! A backtracking matcher for format strings.
+//!
+//! `scanfmt!` compiles its format string into a list of [`Instr`]s and lets
+//! [`run`] decide where each value starts and ends. A value is delimited by
+//! whatever follows it: every occurrence of a following literal, or every
+//! position where the following value could start, is a candidate end. When a
+//! later piece fails to match, the matcher backtracks and tries the next
+//! candidate of an earlier value. Optional groups are matched if possible,
+//! and skipped otherwise, and repetitions take as many values as possible.
+//!
+//! # Cost
+//!
+//! Whether the rest of a pattern matches from a given position does not depend
+//! on how earlier values were split, so the matcher remembers every
+//! `(placeholder, offset)` pair from which matching has failed and never
+//! retries it, and likewise for optional groups. For a pattern with `k`
+//! placeholders and an input of `n` bytes, this bounds the number of calls to
+//! `scan` by `k * (n + 1) * (n + 2) / 2`, and the extra memory by `n + 1` bits
+//! per instruction of the compiled pattern. Patterns whose values are all
+//! delimited by literals that occur once in the input scan each value exactly
+//! once.
+
+use alloc::borrow::{Cow, ToOwned};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{Piece, ScanError, ScanErrorKind};
+
+/// An instruction of a compiled format string.
+#[derive(Debug)]
+pub enum Instr {
+    /// Matches the literal text exactly.
+    Literal {
+        text: Cow<'static, str>,
+        piece: Piece,
+    },
+    /// Scans a value into the slot with the given index. Values with a width
+    /// span exactly that many characters. Values with a class span the
+    /// longest run of characters in it, which is at least one character and
+    /// at most `width` characters.
+    Field {
+        slot: usize,
+        piece: Piece,
+        width: Option<usize>,
+        class: Option<CharClass>,
+    },
+    /// Matches as much whitespace as possible, and at least `min` characters
+    /// of it. `text` is the whitespace in the format string.
+    Whitespace {
+        min: usize,
+        text: Cow<'static, str>,
+        piece: Piece,
+    },
+    /// Matches the instructions up to `end`, the index of the first
+    /// instruction after the group, or skips them if they do not match.
+    Optional { end: usize },
+    /// Matches `separator` and continues at `start` to match another value,
+    /// or continues at the next instruction if that does not match.
+    Repeat {
+        separator: Cow<'static, str>,
+        start: usize,
+    },
+    /// Matches the end of the input. Programs without it match a prefix of
+    /// the input.
+    End,
+}
+
+/// A set of characters, like `[a-z_]` or `[^,]`.
+#[derive(Debug)]
+pub struct CharClass {
+    negated: bool,
+    ranges: Cow<'static, [(char, char)]>,
+    source: Cow<'static, str>,
+}
+
+impl CharClass {
+    /// Creates a class of the characters in the inclusive `ranges`, or of the
+    /// characters not in them if `negated`. `source` is the class as written
+    /// in the format string.
+    pub const fn new(negated: bool, ranges: &'static [(char, char)], source: &'static str) -> Self {
+        Self {
+            negated,
+            ranges: Cow::Borrowed(ranges),
+            source: Cow::Borrowed(source),
+        }
+    }
+
+    /// Like [`CharClass::new`], for classes that are not known at compile
+    /// time.
+    pub(crate) fn owned(negated: bool, ranges: Vec<(char, char)>, source: String) -> Self {
+        Self {
+            negated,
+            ranges: Cow::Owned(ranges),
+            source: Cow::Owned(source),
+        }
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.ranges
+            .iter()
+            .any(|&(start, end)| (start..=end).contains(&c))
+            != self.negated
+    }
+}
+
+/// Storage for the values of the placeholders of a format string, indexed by
+/// slot. The values may borrow from the input, which lives for `'a`.
+pub trait Slots<'a> {
+    /// Tests if `c` is a valid start for the value of `slot`.
+    fn is_valid_start(&self, slot: usize, c: char) -> bool;
+
+    /// Whether longer candidates should be tried first for `slot` when it is
+    /// not delimited by a literal.
+    fn prefers_longest(&self, slot: usize) -> bool;
+
+    /// Scans `s` and stores the value in `slot`, adding it to the values of
+    /// a repetition.
+    fn scan(&mut self, slot: usize, s: &'a str) -> Result<(), ScanError>;
+
+    /// Removes the value that was stored in `slot` last, which is not part of
+    /// the match after backtracking.
+    fn clear(&mut self, slot: usize);
+}
+
+/// Matches `program` against `input`, storing the scanned values in `slots`.
+/// Returns the offset at which matching ended.
+pub fn run<'a, S: Slots<'a>>(
+    program: &[Instr],
+    input: &'a str,
+    slots: &mut S,
+) -> Result<usize, ScanError> {
+    Matcher {
+        program,
+        input,
+        slots,
+        choices: Vec::new(),
+        failed: Vec::new(),
+        best: None,
+    }
+    .run()
+}
+
+/// Tests if a match of `program` can start with `c`, without scanning any
+/// values.
+pub fn is_valid_start<'a, S: Slots<'a>>(program: &[Instr], slots: &S, c: char) -> bool {
+    starts_with(program, 0, slots, c)
+}
+
+fn starts_with<'a, S: Slots<'a>>(program: &[Instr], pc: usize, slots: &S, c: char) -> bool {
+    match program.get(pc) {
+        None => true,
+        Some(Instr::End) => false,
+        Some(Instr::Literal { text, .. }) => text.starts_with(c),
+        Some(Instr::Field {
+            class: Some(class), ..
+        }) => class.contains(c),
+        Some(Instr::Field { slot, .. }) => slots.is_valid_start(*slot, c),
+        Some(Instr::Whitespace { min, .. }) => {
+            c.is_whitespace() || (*min == 0 && starts_with(program, pc + 1, slots, c))
+        }
+        Some(Instr::Optional { end }) => {
+            starts_with(program, pc + 1, slots, c) || starts_with(program, *end, slots, c)
+        }
+        Some(Instr::Repeat { separator, .. }) => {
+            separator.starts_with(c) || starts_with(program, pc + 1, slots, c)
+        }
+    }
+}
+
+/// A value whose candidate ends, or an optional group or repetition whose
+/// alternatives, are still being tried.
+#[derive(Clone, Copy)]
+struct Choice {
+    pc: usize,
+    start: usize,
+    /// The candidate end that was tried last. For optional groups and
+    /// repetitions, the instruction at which matching continued last.
+    end: Option<usize>,
+    /// Whether the value scanned at the candidate end is stored in its slot.
+    scanned: bool,
+}
+
+struct Matcher<'p, 'i, 's, S> {
+    program: &'p [Instr],
+    input: &'i str,
+    slots: &'s mut S,
+    choices: Vec<Choice>,
+    /// Bit set of `(pc, offset)` pairs from which matching has failed.
+    failed: Vec<u64>,
+    /// The error that got the furthest into the input.
+    best: Option<ScanError>,
+}
+
+impl<'p, 'i, S: Slots<'i>> Matcher<'p, 'i, '_, S> {
+    fn run(mut self) -> Result<usize, ScanError> {
+        let program = self.program;
+        let mut pc = 0;
+        let mut pos = 0;
+        loop {
+            match program.get(pc) {
+                None => return Ok(pos),
+                Some(Instr::Literal { text, piece }) => {
+                    if self.input[pos..].starts_with(&**text) {
+                        pc += 1;
+                        pos += text.len();
+                        continue;
+                    }
+                    let kind = if self.input.len() - pos < text.len() {
+                        ScanErrorKind::LiteralNotFound
+                    } else {
+                        ScanErrorKind::LiteralMismatch
+                    };
+                    self.fail(kind, pos, piece, Some(text));
+                }
+                Some(Instr::Whitespace { min, text, piece }) => {
+                    let rest = &self.input[pos..];
+                    let len = rest.len() - rest.trim_start().len();
+                    if rest[..len].chars().count() >= *min {
+                        pc += 1;
+                        pos += len;
+                        continue;
+                    }
+                    self.fail(ScanErrorKind::LiteralMismatch, pos, piece, Some(text));
+                }
+                Some(Instr::End) => {
+                    if pos == self.input.len() {
+                        pc += 1;
+                        continue;
+                    }
+                    self.trailing_input(pos);
+                }
+                Some(Instr::Field { .. } | Instr::Optional { .. } | Instr::Repeat { .. }) => {
+                    if !self.has_failed(pc, pos) {
+                        self.choices.push(Choice {
+                            pc,
+                            start: pos,
+                            end: None,
+                            scanned: false,
+                        });
+                    }
+                }
+            }
+
+            (pc, pos) = self.backtrack()?;
+        }
+    }
+
+    /// Tries the remaining candidates of the innermost choice, popping choices
+    /// whose candidates are exhausted. Returns where to continue matching.
+    fn backtrack(&mut self) -> Result<(usize, usize), ScanError> {
+        while let Some(choice) = self.choices.last().copied() {
+            if let Instr::Optional { .. } | Instr::Repeat { .. } = self.program[choice.pc] {
+                let Some((pc, pos)) = self.next_alternative(choice) else {
+                    self.set_failed(choice.pc, choice.start);
+                    self.choices.pop();
+                    continue;
+                };
+                self.choices.last_mut().unwrap().end = Some(pc);
+                return Ok((pc, pos));
+            }
+
+            let (slot, piece) = self.field(choice.pc);
+            if choice.scanned {
+                self.slots.clear(slot);
+                self.choices.last_mut().unwrap().scanned = false;
+            }
+
+            let Some(end) = self.next_end(choice) else {
+                if choice.end.is_none() {
+                    self.no_candidates(choice.pc, choice.start, piece);
+                }
+                self.set_failed(choice.pc, choice.start);
+                self.choices.pop();
+                continue;
+            };
+
+            self.choices.last_mut().unwrap().end = Some(end);
+            match self.slots.scan(slot, &self.input[choice.start..end]) {
+                Ok(()) => {
+                    self.choices.last_mut().unwrap().scanned = true;
+                    return Ok((choice.pc + 1, end));
+                }
+                Err(e) => self.record(locate(e, choice.start, piece)),
+            }
+        }
+
+        Err(self
+            .best
+            .take()
+            .unwrap_or_else(|| ScanErrorKind::Eof.into()))
+    }
+
+    /// Finds where to continue matching after the alternative of the optional
+    /// group or repetition of `choice` that was tried last.
+    fn next_alternative(&self, choice: Choice) -> Option<(usize, usize)> {
+        match (&self.program[choice.pc], choice.end) {
+            // a group is matched before it is skipped.
+            (Instr::Optional { .. }, None) => Some((choice.pc + 1, choice.start)),
+            (Instr::Optional { end }, Some(pc)) if pc == choice.pc + 1 => {
+                Some((*end, choice.start))
+            }
+            // another value is matched before the repetition ends.
+            (Instr::Repeat { separator, start }, None)
+                if self.input[choice.start..].starts_with(&**separator) =>
+            {
+                Some((*start, choice.start + separator.len()))
+            }
+            (Instr::Repeat { start, .. }, Some(pc)) if pc != *start => None,
+            (Instr::Repeat { .. }, _) => Some((choice.pc + 1, choice.start)),
+            _ => None,
+        }
+    }
+
+    /// Finds the candidate end of the value of `choice` to try after the one
+    /// tried last.
+    fn next_end(&self, choice: Choice) -> Option<usize> {
+        let input = self.input;
+        let (slot, _) = self.field(choice.pc);
+
+        match &self.program[choice.pc] {
+            Instr::Field {
+                class: Some(class),
+                width,
+                ..
+            } => {
+                if choice.end.is_some() {
+                    return None;
+                }
+                let len = input[choice.start..]
+                    .chars()
+                    .take(width.unwrap_or(usize::MAX))
+                    .take_while(|&c| class.contains(c))
+                    .map(char::len_utf8)
+                    .sum::<usize>();
+                return (len > 0).then_some(choice.start + len);
+            }
+            Instr::Field {
+                width: Some(width), ..
+            } => {
+                return match choice.end {
+                    None => nth_boundary(input, choice.start, *width),
+                    Some(_) => None,
+                };
+            }
+            _ => {}
+        }
+
+        match self.program.get(choice.pc + 1) {
+            // the last value extends to the end of the input.
+            Some(Instr::End) => match choice.end {
+                None => Some(input.len()),
+                Some(_) => None,
+            },
+            // when matching a prefix, the last value is as long as it can be.
+            None => match choice.end {
+                None => Some(input.len()),
+                Some(end) if end == choice.start => None,
+                Some(end) => prev_boundary(input, end),
+            },
+            // values followed by a literal end at its occurrences, first to last.
+            Some(Instr::Literal { text, .. }) => {
+                let from = match choice.end {
+                    None => choice.start,
+                    Some(end) => next_boundary(input, end)?,
+                };
+                input[from..].find(&**text).map(|idx| from + idx)
+            }
+            // other values end where what follows them can start.
+            Some(_) => {
+                let is_candidate = |end: usize| self.can_start(choice.pc + 1, end);
+                let mut end = choice.end;
+                if self.slots.prefers_longest(slot) {
+                    loop {
+                        end = match end {
+                            None => Some(input.len()),
+                            Some(end) if end == choice.start => return None,
+                            Some(end) => prev_boundary(input, end),
+                        };
+                        if is_candidate(end?) {
+                            return end;
+                        }
+                    }
+                } else {
+                    loop {
+                        end = match end {
+                            None => Some(choice.start),
+                            Some(end) => next_boundary(input, end),
+                        };
+                        if is_candidate(end?) {
+                            return end;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tests if matching the instruction at `pc` could succeed at `pos`,
+    /// without scanning any values.
+    fn can_start(&self, pc: usize, pos: usize) -> bool {
+        let rest = &self.input[pos..];
+        match self.program.get(pc) {
+            None => true,
+            Some(Instr::Optional { end }) => {
+                self.can_start(pc + 1, pos) || self.can_start(*end, pos)
+            }
+            Some(Instr::Repeat { separator, .. }) => {
+                rest.starts_with(&**separator) || self.can_start(pc + 1, pos)
+            }
+            Some(Instr::End) => rest.is_empty(),
+            Some(Instr::Literal { text, .. }) => rest.starts_with(&**text),
+            Some(Instr::Field {
+                class: Some(class), ..
+            }) => rest.chars().next().is_some_and(|c| class.contains(c)),
+            Some(Instr::Field { slot, .. }) => rest
+                .chars()
+                .next()
+                .is_none_or(|c| self.slots.is_valid_start(*slot, c)),
+            Some(Instr::Whitespace { min, .. }) => {
+                rest.starts_with(char::is_whitespace) || (*min == 0 && self.can_start(pc + 1, pos))
+            }
+        }
+    }
+
+    fn field(&self, pc: usize) -> (usize, &'p Piece) {
+        match &self.program[pc] {
+            Instr::Field { slot, piece, .. } => (*slot, piece),
+            _ => unreachable!(),
+        }
+    }
+
+    fn trailing_input(&mut self, offset: usize) {
+        if !self.is_further(Some(offset)) {
+            return;
+        }
+        self.best = Some(ScanError {
+            kind: ScanErrorKind::TrailingInput(self.input[offset..].to_owned()),
+            offset: Some(offset),
+            piece: None,
+            expected: None,
+            line: None,
+        });
+    }
+
+    fn no_candidates(&mut self, pc: usize, start: usize, piece: &Piece) {
+        let program = self.program;
+        match &program[pc] {
+            Instr::Field {
+                class: Some(class), ..
+            } => {
+                return self.fail(
+                    ScanErrorKind::ClassMismatch,
+                    start,
+                    piece,
+                    Some(&class.source),
+                );
+            }
+            Instr::Field { width: Some(_), .. } => {
+                return self.fail(ScanErrorKind::Eof, start, piece, None);
+            }
+            _ => {}
+        }
+        match program.get(pc + 1) {
+            Some(Instr::Literal { text, .. }) => {
+                self.fail(ScanErrorKind::LiteralNotFound, start, piece, Some(text))
+            }
+            _ => self.fail(ScanErrorKind::Eof, start, piece, None),
+        }
+    }
+
+    fn fail(
+        &mut self,
+        kind: ScanErrorKind,
+        offset: usize,
+        piece: &Piece,
+        expected: Option<&Cow<'static, str>>,
+    ) {
+        // the piece and the expected text are only copied into errors that
+        // are kept.
+        if self.is_further(Some(offset)) {
+            self.record(ScanError {
+                kind,
+                offset: Some(offset),
+                piece: Some(piece.clone()),
+                expected: expected.cloned(),
+                line: None,
+            })
+        }
+    }
+
+    /// Keeps the error that got the furthest into the input.
+    fn record(&mut self, e: ScanError) {
+        if self.is_further(e.offset) {
+            self.best = Some(e);
+        }
+    }
+
+    fn is_further(&self, offset: Option<usize>) -> bool {
+        self.best.as_ref().is_none_or(|best| best.offset < offset)
+    }
+
+    fn failed_bit(&self, pc: usize, pos: usize) -> usize {
+        pc * (self.input.len() + 1) + pos
+    }
+
+    fn has_failed(&self, pc: usize, pos: usize) -> bool {
+        let bit = self.failed_bit(pc, pos);
+        self.failed
+            .get(bit / 64)
+            .is_some_and(|word| word & (1 << (bit % 64)) != 0)
+    }
+
+    fn set_failed(&mut self, pc: usize, pos: usize) {
+        if self.failed.is_empty() {
+            let bits = self.program.len() * (self.input.len() + 1);
+            self.failed.resize(bits.div_ceil(64), 0);
+        }
+        let bit = self.failed_bit(pc, pos);
+        self.failed[bit / 64] |= 1 << (bit % 64);
+    }
+}
+
+/// Locates an error returned from scanning the placeholder `piece`, which
+/// started at `offset`. Errors of values that are scanned with patterns of
+/// their own are already located in the value, and are only moved.
+fn locate(mut e: ScanError, offset: usize, piece: &Piece) -> ScanError {
+    match &mut e.offset {
+        Some(inner) => *inner += offset,
+        None => {
+            e.offset = Some(offset);
+            e.piece = Some(piece.clone());
+        }
+    }
+    e
+}
+
+fn next_boundary(s: &str, idx: usize) -> Option<usize> {
+    s[idx..].chars().next().map(|c| idx + c.len_utf8())
+}
+
+/// Finds the char boundary `n` characters after `idx`.
+fn nth_boundary(s: &str, idx: usize, n: usize) -> Option<usize> {
+    let mut chars = s[idx..].char_indices();
+    match chars.nth(n) {
+        Some((len, _)) => Some(idx + len),
+        None if s[idx..].chars().count() == n => Some(s.len()),
+        None => None,
+    }
+}
+
+fn prev_boundary(s: &str, idx: usize) -> Option<usize> {
+    s[..idx].char_indices().next_back().map(|(i, _)| i)
+}
@@ -0,0 +1,552 @@
+//! Patterns that are compiled at runtime.
+
+use alloc::borrow::{Cow, ToOwned};
+use alloc::format;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::io::BufRead;
+
+use scanfmt_syntax::{self as syntax, split_whitespace_runs, Argument, Spec, Whitespace};
+
+use crate::macro_support::Discard;
+use crate::matcher::{self, CharClass, Instr, Slots};
+#[cfg(feature = "std")]
+use crate::Lines;
+use crate::{Piece, Scan, ScanBinary, ScanError, ScanLowerHex, ScanOctal, ScanUpperHex};
+
+pub use scanfmt_syntax::Error as PatternError;
+
+/// A format string that is compiled at runtime, and scans into a tuple of
+/// type `T`.
+///
+/// The format string has the same syntax as the one of [`scanfmt!`], except
+/// that placeholders cannot have types, since the types of the values are the
+/// elements of `T`. Discarded placeholders have no element. Placeholders with
+/// a radix, like `{:x}`, scan integers in it.
+///
+/// ```
+/// use scanfmt::Pattern;
+///
+/// let pattern = Pattern::<(u32, String)>::new("{id}: {name}")?;
+/// assert_eq!((7, "alice".to_owned()), pattern.scan("7: alice")?);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// [`scanfmt!`]: crate::scanfmt
+pub struct Pattern<T> {
+    program: Vec<Instr>,
+    /// For each slot of the program, the index of its value in `T`, or `None`
+    /// if the value is discarded.
+    values: Vec<Option<usize>>,
+    /// For each slot of the program, the radix of its value, if any.
+    radixes: Vec<Option<Radix>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// A tuple of the values scanned by a [`Pattern`], one for each placeholder
+/// that is not discarded, in order.
+pub trait Values: Sized {
+    #[doc(hidden)]
+    type Slots: Default;
+    #[doc(hidden)]
+    const KINDS: &'static [Kind];
+    #[doc(hidden)]
+    fn supports_radix(value: usize, radix: Radix) -> bool;
+    #[doc(hidden)]
+    fn is_valid_start(value: usize, radix: Option<Radix>, c: char) -> bool;
+    #[doc(hidden)]
+    fn prefers_longest(value: usize) -> bool;
+    #[doc(hidden)]
+    fn scan(
+        slots: &mut Self::Slots,
+        value: usize,
+        radix: Option<Radix>,
+        s: &str,
+    ) -> Result<(), ScanError>;
+    #[doc(hidden)]
+    fn clear(slots: &mut Self::Slots, value: usize);
+    #[doc(hidden)]
+    fn finish(slots: Self::Slots) -> Self;
+}
+
+/// A value in the [`Values`] of a [`Pattern`]: `Option<T>` for placeholders in
+/// optional groups, `Vec<T>` for repetitions, and `T` otherwise.
+pub trait Capture: Sized {
+    #[doc(hidden)]
+    type Slot: Default;
+    #[doc(hidden)]
+    const KIND: Kind;
+    #[doc(hidden)]
+    fn supports_radix(radix: Radix) -> bool;
+    #[doc(hidden)]
+    fn is_valid_start(radix: Option<Radix>, c: char) -> bool;
+    #[doc(hidden)]
+    fn prefers_longest() -> bool;
+    #[doc(hidden)]
+    fn scan(slot: &mut Self::Slot, radix: Option<Radix>, s: &str) -> Result<(), ScanError>;
+    #[doc(hidden)]
+    fn clear(slot: &mut Self::Slot);
+    #[doc(hidden)]
+    fn finish(slot: Self::Slot) -> Self;
+}
+
+/// How the values of a placeholder are collected.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Single,
+    Optional,
+    Repeated,
+}
+
+/// The radix of a placeholder, like `{:x}`.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Binary,
+    Octal,
+    LowerHex,
+    UpperHex,
+}
+
+/// The functions scanning a `T` in a [`Radix`], which are the ones of its
+/// [`ScanBinary`], [`ScanOctal`], [`ScanLowerHex`] or [`ScanUpperHex`]
+/// implementation.
+#[doc(hidden)]
+pub struct RadixScan<T> {
+    pub is_valid_start: fn(char) -> bool,
+    pub scan: fn(&str) -> Result<T, ScanError>,
+}
+
+impl<T: ScanBinary + ScanOctal + ScanLowerHex + ScanUpperHex> RadixScan<T> {
+    pub fn new(radix: Radix) -> Self {
+        match radix {
+            Radix::Binary => Self {
+                is_valid_start: <T as ScanBinary>::is_valid_start,
+                scan: <T as ScanBinary>::scan,
+            },
+            Radix::Octal => Self {
+                is_valid_start: <T as ScanOctal>::is_valid_start,
+                scan: <T as ScanOctal>::scan,
+            },
+            Radix::LowerHex => Self {
+                is_valid_start: <T as ScanLowerHex>::is_valid_start,
+                scan: <T as ScanLowerHex>::scan,
+            },
+            Radix::UpperHex => Self {
+                is_valid_start: <T as ScanUpperHex>::is_valid_start,
+                scan: <T as ScanUpperHex>::scan,
+            },
+        }
+    }
+}
+
+impl<T: Values> Pattern<T> {
+    /// Compiles the format string `fmt`.
+    pub fn new(fmt: &str) -> Result<Self, PatternError> {
+        let pieces = syntax::parse(fmt)?;
+        let mut values = Vec::new();
+        let mut radixes = Vec::new();
+        for fmt in formats(&pieces) {
+            if fmt.ty.is_some() {
+                return Err(PatternError::new(format!(
+                    "`{}` has a type, but the types of values are given by the tuple type",
+                    fmt.source
+                )));
+            }
+            let radix = match fmt.spec {
+                Spec::Default | Spec::Class(_) => None,
+                Spec::Binary => Some(Radix::Binary),
+                Spec::Octal => Some(Radix::Octal),
+                Spec::LowerHex => Some(Radix::LowerHex),
+                Spec::UpperHex => Some(Radix::UpperHex),
+            };
+            radixes.push(radix);
+            if let Argument::Index(_) = fmt.argument {
+                return Err(PatternError::new(format!(
+                    "`{}` refers to an argument, but patterns have no arguments",
+                    fmt.source
+                )));
+            }
+            if let Argument::Discard = fmt.argument {
+                values.push(None);
+                continue;
+            }
+
+            let value = values.iter().flatten().count();
+            let expected = T::KINDS.get(value).copied();
+            if expected.is_some() && expected != Some(kind(fmt)) {
+                let ty = match kind(fmt) {
+                    Kind::Single => "a value that is not an `Option` or a `Vec`",
+                    Kind::Optional => "an `Option`",
+                    Kind::Repeated => "a `Vec`",
+                };
+                return Err(PatternError::new(format!(
+                    "`{}` scans into {}, which is not the type of element {} of the tuple",
+                    fmt.source, ty, value
+                )));
+            }
+            if let (Some(_), Some(radix)) = (expected, radix) {
+                if !T::supports_radix(value, radix) {
+                    return Err(PatternError::new(format!(
+                        "`{}` has a radix, but element {} of the tuple cannot be scanned in it",
+                        fmt.source, value
+                    )));
+                }
+            }
+            values.push(Some(value));
+        }
+
+        let count = values.iter().flatten().count();
+        if count != T::KINDS.len() {
+            return Err(PatternError::new(format!(
+                "the pattern has {} values, but the tuple has {} elements",
+                count,
+                T::KINDS.len()
+            )));
+        }
+
+        Ok(Self {
+            program: compile(&pieces),
+            values,
+            radixes,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Scans the whole of `input`.
+    pub fn scan(&self, input: &str) -> Result<T, ScanError> {
+        let mut slots = TupleSlots::<T> {
+            slots: T::Slots::default(),
+            values: &self.values,
+            radixes: &self.radixes,
+        };
+        matcher::run(&self.program, input, &mut slots)?;
+        Ok(T::finish(slots.slots))
+    }
+
+    /// Scans each line of `reader`, without its line terminator.
+    #[cfg(feature = "std")]
+    pub fn lines<R: BufRead>(
+        &self,
+        reader: R,
+    ) -> Lines<R, impl FnMut(&str) -> Result<T, ScanError> + '_> {
+        Lines::new(reader, move |line: &str| self.scan(line))
+    }
+}
+
+impl<T> core::fmt::Debug for Pattern<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Pattern")
+            .field("program", &self.program)
+            .finish_non_exhaustive()
+    }
+}
+
+struct TupleSlots<'a, T: Values> {
+    slots: T::Slots,
+    values: &'a [Option<usize>],
+    radixes: &'a [Option<Radix>],
+}
+
+impl<T: Values> Slots<'_> for TupleSlots<'_, T> {
+    fn is_valid_start(&self, slot: usize, c: char) -> bool {
+        let radix = self.radixes[slot];
+        match self.values[slot] {
+            Some(value) => T::is_valid_start(value, radix, c),
+            None => is_valid_start_in::<Discard>(radix, c),
+        }
+    }
+
+    // like in the macros, values with a radix prefer the longest candidate.
+    fn prefers_longest(&self, slot: usize) -> bool {
+        self.radixes[slot].is_some() || self.values[slot].is_some_and(T::prefers_longest)
+    }
+
+    fn scan(&mut self, slot: usize, s: &str) -> Result<(), ScanError> {
+        let radix = self.radixes[slot];
+        match self.values[slot] {
+            Some(value) => T::scan(&mut self.slots, value, radix, s),
+            None => scan_in::<Discard>(radix, s).map(drop),
+        }
+    }
+
+    fn clear(&mut self, slot: usize) {
+        if let Some(value) = self.values[slot] {
+            T::clear(&mut self.slots, value);
+        }
+    }
+}
+
+impl<T: Scan> Capture for T {
+    type Slot = Option<T>;
+    const KIND: Kind = Kind::Single;
+
+    fn supports_radix(radix: Radix) -> bool {
+        T::radix(radix).is_some()
+    }
+
+    fn is_valid_start(radix: Option<Radix>, c: char) -> bool {
+        is_valid_start_in::<T>(radix, c)
+    }
+
+    fn prefers_longest() -> bool {
+        T::prefers_longest()
+    }
+
+    fn scan(slot: &mut Self::Slot, radix: Option<Radix>, s: &str) -> Result<(), ScanError> {
+        *slot = Some(scan_in(radix, s)?);
+        Ok(())
+    }
+
+    fn clear(slot: &mut Self::Slot) {
+        *slot = None;
+    }
+
+    fn finish(slot: Self::Slot) -> Self {
+        slot.unwrap()
+    }
+}
+
+impl<T: Scan> Capture for Option<T> {
+    type Slot = Option<T>;
+    const KIND: Kind = Kind::Optional;
+
+    fn supports_radix(radix: Radix) -> bool {
+        T::radix(radix).is_some()
+    }
+
+    fn is_valid_start(radix: Option<Radix>, c: char) -> bool {
+        is_valid_start_in::<T>(radix, c)
+    }
+
+    fn prefers_longest() -> bool {
+        T::prefers_longest()
+    }
+
+    fn scan(slot: &mut Self::Slot, radix: Option<Radix>, s: &str) -> Result<(), ScanError> {
+        *slot = Some(scan_in(radix, s)?);
+        Ok(())
+    }
+
+    fn clear(slot: &mut Self::Slot) {
+        *slot = None;
+    }
+
+    fn finish(slot: Self::Slot) -> Self {
+        slot
+    }
+}
+
+impl<T: Scan> Capture for Vec<T> {
+    type Slot = Vec<T>;
+    const KIND: Kind = Kind::Repeated;
+
+    fn supports_radix(radix: Radix) -> bool {
+        T::radix(radix).is_some()
+    }
+
+    fn is_valid_start(radix: Option<Radix>, c: char) -> bool {
+        is_valid_start_in::<T>(radix, c)
+    }
+
+    fn prefers_longest() -> bool {
+        T::prefers_longest()
+    }
+
+    fn scan(slot: &mut Self::Slot, radix: Option<Radix>, s: &str) -> Result<(), ScanError> {
+        slot.push(scan_in(radix, s)?);
+        Ok(())
+    }
+
+    fn clear(slot: &mut Self::Slot) {
+        slot.pop();
+    }
+
+    fn finish(slot: Self::Slot) -> Self {
+        slot
+    }
+}
+
+macro_rules! tuple_values {
+    ($(($($idx:tt $t:ident),*))*) => {$(
+        #[allow(unused_variables, clippy::unused_unit)]
+        impl<$($t: Capture),*> Values for ($($t,)*) {
+            type Slots = ($($t::Slot,)*);
+            const KINDS: &'static [Kind] = &[$($t::KIND),*];
+
+            fn supports_radix(value: usize, radix: Radix) -> bool {
+                match value {
+                    $($idx => $t::supports_radix(radix),)*
+                    _ => unreachable!(),
+                }
+            }
+
+            fn is_valid_start(value: usize, radix: Option<Radix>, c: char) -> bool {
+                match value {
+                    $($idx => $t::is_valid_start(radix, c),)*
+                    _ => unreachable!(),
+                }
+            }
+
+            fn prefers_longest(value: usize) -> bool {
+                match value {
+                    $($idx => $t::prefers_longest(),)*
+                    _ => unreachable!(),
+                }
+            }
+
+            fn scan(
+                slots: &mut Self::Slots,
+                value: usize,
+                radix: Option<Radix>,
+                s: &str,
+            ) -> Result<(), ScanError> {
+                match value {
+                    $($idx => $t::scan(&mut slots.$idx, radix, s),)*
+                    _ => unreachable!(),
+                }
+            }
+
+            fn clear(slots: &mut Self::Slots, value: usize) {
+                match value {
+                    $($idx => $t::clear(&mut slots.$idx),)*
+                    _ => unreachable!(),
+                }
+            }
+
+            fn finish(slots: Self::Slots) -> Self {
+                ($($t::finish(slots.$idx),)*)
+            }
+        }
+    )*};
+}
+
+tuple_values! {
+    ()
+    (0 A)
+    (0 A, 1 B)
+    (0 A, 1 B, 2 C)
+    (0 A, 1 B, 2 C, 3 D)
+    (0 A, 1 B, 2 C, 3 D, 4 E)
+    (0 A, 1 B, 2 C, 3 D, 4 E, 5 F)
+    (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G)
+    (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H)
+    (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I)
+    (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J)
+    (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K)
+    (0 A, 1 B, 2 C, 3 D, 4 E, 5 F, 6 G, 7 H, 8 I, 9 J, 10 K, 11 L)
+}
+
+/// Tests if `c` is a valid start for a `T` in `radix`, if any.
+fn is_valid_start_in<T: Scan>(radix: Option<Radix>, c: char) -> bool {
+    match radix {
+        None => T::is_valid_start(c),
+        Some(radix) => T::radix(radix).is_some_and(|scan| (scan.is_valid_start)(c)),
+    }
+}
+
+/// Scans `s` as a `T`, in `radix` if any.
+fn scan_in<T: Scan>(radix: Option<Radix>, s: &str) -> Result<T, ScanError> {
+    match radix {
+        None => T::scan(s),
+        // patterns only have radixes that their values can be scanned in.
+        Some(radix) => (T::radix(radix).unwrap().scan)(s),
+    }
+}
+
+pub(crate) fn formats(pieces: &[syntax::Piece]) -> impl Iterator<Item = &syntax::Format> {
+    pieces.iter().filter_map(|piece| match piece {
+        syntax::Piece::Fmt(fmt) => Some(fmt),
+        _ => None,
+    })
+}
+
+pub(crate) fn kind(fmt: &syntax::Format) -> Kind {
+    match (&fmt.separator, fmt.optional) {
+        (Some(_), _) => Kind::Repeated,
+        (None, true) => Kind::Optional,
+        (None, false) => Kind::Single,
+    }
+}
+
+/// Compiles the pieces of a format string into a program that matches the
+/// whole input, in which the placeholders have slots in order.
+pub(crate) fn compile(pieces: &[syntax::Piece]) -> Vec<Instr> {
+    let mut program = Vec::new();
+    // indices of the `Optional` instructions of the open groups, which are
+    // filled in once the end of the group is known.
+    let mut groups = Vec::new();
+    let mut slot = 0;
+    let mut index = 0;
+
+    for piece in pieces {
+        match piece {
+            syntax::Piece::OptionalStart => {
+                groups.push(program.len());
+                program.push(Instr::Optional { end: 0 });
+                continue;
+            }
+            syntax::Piece::OptionalEnd => {
+                let start = groups.pop().unwrap();
+                program[start] = Instr::Optional { end: program.len() };
+                continue;
+            }
+            syntax::Piece::Lit(lit, Whitespace::Exact) => program.push(Instr::Literal {
+                text: Cow::Owned(lit.clone()),
+                piece: Piece::owned(index, lit.clone()),
+            }),
+            syntax::Piece::Lit(lit, Whitespace::Flexible(min)) => {
+                for (text, is_ws) in split_whitespace_runs(lit) {
+                    let text = Cow::Owned(text.to_owned());
+                    let piece = Piece::owned(index, lit.clone());
+                    program.push(if is_ws {
+                        Instr::Whitespace {
+                            min: *min,
+                            text,
+                            piece,
+                        }
+                    } else {
+                        Instr::Literal { text, piece }
+                    });
+                }
+            }
+            syntax::Piece::Fmt(fmt) => {
+                let field = Instr::Field {
+                    slot,
+                    piece: Piece::owned(index, fmt.source.clone()),
+                    width: fmt.width,
+                    class: match &fmt.spec {
+                        Spec::Class(class) => Some(CharClass::owned(
+                            class.negated,
+                            class.ranges.clone(),
+                            class.source.clone(),
+                        )),
+                        _ => None,
+                    },
+                };
+                slot += 1;
+                match &fmt.separator {
+                    // a repetition has any number of values, including none.
+                    Some(separator) => {
+                        let start = program.len() + 1;
+                        program.extend([
+                            Instr::Optional { end: start + 2 },
+                            field,
+                            Instr::Repeat {
+                                separator: Cow::Owned(separator.clone()),
+                                start,
+                            },
+                        ]);
+                    }
+                    None => program.push(field),
+                }
+            }
+        }
+        index += 1;
+    }
+
+    program.push(Instr::End);
+    program
+}
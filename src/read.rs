@@ -0,0 +1,70 @@
+//! Scanning lines read from a `BufRead`.
+
+use std::io::{self, BufRead};
+
+use crate::ScanError;
+
+/// Reads a line from `reader` into `buf`, replacing its contents, and strips
+/// the line terminator, either `\n` or `\r\n`. Returns `false` at the end of
+/// the input.
+pub(crate) fn read_line<R: BufRead + ?Sized>(reader: &mut R, buf: &mut String) -> io::Result<bool> {
+    buf.clear();
+    if reader.read_line(buf)? == 0 {
+        return Ok(false);
+    }
+    if buf.ends_with('\n') {
+        buf.pop();
+        if buf.ends_with('\r') {
+            buf.pop();
+        }
+    }
+    Ok(true)
+}
+
+/// An iterator over the values scanned from each line of a reader, created by
+/// [`scan_lines!`] or [`Pattern::lines`].
+///
+/// The lines are read into one buffer, which is reused. Errors carry the
+/// number of the line they occurred on.
+///
+/// [`scan_lines!`]: crate::scan_lines
+/// [`Pattern::lines`]: crate::Pattern::lines
+pub struct Lines<R, F> {
+    reader: R,
+    scan: F,
+    buf: String,
+    line: usize,
+}
+
+impl<R, F> Lines<R, F> {
+    pub(crate) fn new(reader: R, scan: F) -> Self {
+        Self {
+            reader,
+            scan,
+            buf: String::new(),
+            line: 0,
+        }
+    }
+
+    /// The number of lines read so far.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl<T, R: BufRead, F: FnMut(&str) -> Result<T, ScanError>> Iterator for Lines<R, F> {
+    type Item = Result<T, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.line += 1;
+        let result = match read_line(&mut self.reader, &mut self.buf) {
+            Ok(false) => {
+                self.line -= 1;
+                return None;
+            }
+            Ok(true) => (self.scan)(&self.buf),
+            Err(e) => Err(e.into()),
+        };
+        Some(result.map_err(|e| e.with_line(self.line)))
+    }
+}
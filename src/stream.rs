@@ -0,0 +1,195 @@
+//! Scanning records from an `io::Read` without reading all of it first.
+
+use std::io::{self, Read};
+
+use crate::{Pattern, ScanError, Values};
+
+/// The number of bytes read from the reader at once, at least.
+const CHUNK: usize = 8 * 1024;
+
+/// Splits the input of a reader into records ending with a terminator, which
+/// are read into a growable buffer as they are needed, so that records can
+/// span any number of reads.
+///
+/// The records are scanned as a whole: with [`scan!`] on the records returned
+/// by [`Scanner::next_record`], or with a [`Pattern`] by [`Scanner::scan`].
+///
+/// ```
+/// use scanfmt::{scan, Scanner};
+///
+/// let mut scanner = Scanner::new("1,2;3,4;".as_bytes(), ";");
+/// let mut sum = 0;
+/// while let Some(record) = scanner.next_record() {
+///     let (a, b): (u32, u32) = scan!(record?, "{},{}")?;
+///     sum += a * b;
+/// }
+/// assert_eq!(14, sum);
+/// # Ok::<(), scanfmt::ScanError>(())
+/// ```
+///
+/// [`scan!`]: crate::scan
+pub struct Scanner<R> {
+    reader: R,
+    records: Records,
+}
+
+/// The buffer of a scanner, which splits the input read into it into records.
+pub(crate) struct Records {
+    terminator: String,
+    buf: Vec<u8>,
+    /// The length of the input in the buffer, after which it is spare space.
+    filled: usize,
+    /// The start of the input that is not consumed yet.
+    start: usize,
+    /// Where to continue searching for the terminator.
+    searched: usize,
+    eof: bool,
+    count: usize,
+}
+
+/// What is next in the input of [`Records`].
+pub(crate) enum Next {
+    /// A record ending at the first index, followed by the next record at the
+    /// second one.
+    Record(usize, usize),
+    /// The end of the input.
+    End,
+    /// More input needs to be read to find the end of the record.
+    Fill,
+}
+
+impl<R: Read> Scanner<R> {
+    /// Creates a scanner of the records of `reader` that end with
+    /// `terminator`. The last record may end with the input instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `terminator` is empty.
+    pub fn new(reader: R, terminator: &str) -> Self {
+        Self {
+            reader,
+            records: Records::new(terminator),
+        }
+    }
+
+    /// Reads the next record, without its terminator. Returns `None` at the
+    /// end of the input.
+    ///
+    /// Reading fails with an error of kind [`ScanErrorKind::Io`] if the record
+    /// is not valid UTF-8.
+    ///
+    /// [`ScanErrorKind::Io`]: crate::ScanErrorKind::Io
+    pub fn next_record(&mut self) -> Option<Result<&str, ScanError>> {
+        loop {
+            match self.records.next() {
+                Next::Record(end, next) => return Some(self.records.take(end, next)),
+                Next::End => return None,
+                Next::Fill => loop {
+                    match self.reader.read(self.records.spare()) {
+                        Ok(n) => {
+                            self.records.fill(n);
+                            break;
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => return Some(Err(e.into())),
+                    }
+                },
+            }
+        }
+    }
+
+    /// Reads the next record and scans the whole of it with `pattern`.
+    /// Returns `None` at the end of the input.
+    pub fn scan<T: Values>(&mut self, pattern: &Pattern<T>) -> Option<Result<T, ScanError>> {
+        Some(self.next_record()?.and_then(|record| pattern.scan(record)))
+    }
+
+    /// The number of records read so far.
+    pub fn records(&self) -> usize {
+        self.records.count()
+    }
+
+    /// Returns the reader, dropping the input that was read but not consumed.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl Records {
+    pub(crate) fn new(terminator: &str) -> Self {
+        assert!(!terminator.is_empty(), "the terminator must not be empty");
+        Self {
+            terminator: terminator.to_owned(),
+            buf: Vec::new(),
+            filled: 0,
+            start: 0,
+            searched: 0,
+            eof: false,
+            count: 0,
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> Next {
+        let len = self.terminator.len();
+        let terminator = self.terminator.as_bytes();
+        if let Some(idx) = self.buf[self.searched..self.filled]
+            .windows(len)
+            .position(|window| window == terminator)
+        {
+            let end = self.searched + idx;
+            return Next::Record(end, end + len);
+        }
+        if self.eof {
+            return if self.start == self.filled {
+                Next::End
+            } else {
+                Next::Record(self.filled, self.filled)
+            };
+        }
+        // the terminator may start in what was read so far.
+        self.searched = self.start.max(self.filled.saturating_sub(len - 1));
+        Next::Fill
+    }
+
+    /// Consumes the record ending at `end`, which is followed by the next one
+    /// at `next`.
+    pub(crate) fn take(&mut self, end: usize, next: usize) -> Result<&str, ScanError> {
+        let record = &self.buf[self.start..end];
+        self.start = next;
+        self.searched = next;
+        self.count += 1;
+        std::str::from_utf8(record)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+    }
+
+    /// Returns the space to read more input into. When there is not enough of
+    /// it, the consumed input is dropped first, and the buffer doubles in size
+    /// while a record does not fit in it.
+    pub(crate) fn spare(&mut self) -> &mut [u8] {
+        if self.buf.len() - self.filled < CHUNK {
+            if self.start > 0 {
+                self.buf.copy_within(self.start..self.filled, 0);
+                self.filled -= self.start;
+                self.searched -= self.start;
+                self.start = 0;
+            }
+            let len = self.filled + CHUNK.max(self.filled);
+            if self.buf.len() < len {
+                self.buf.resize(len, 0);
+            }
+        }
+        &mut self.buf[self.filled..]
+    }
+
+    /// Adds `n` bytes that were read into the spare space to the input, where
+    /// `0` is the end of the input.
+    pub(crate) fn fill(&mut self, n: usize) {
+        self.filled += n;
+        self.eof = n == 0;
+    }
+
+    /// The number of records taken so far.
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+}
@@ -39,23 +39,930 @@ fn int_after_string() -> Result<(), Box<dyn Error>> {
 }
 
 #[test]
-fn string_after_int() {
-    // string is greedy, so it eats everything, making int parsing fail.
+fn string_after_int() -> Result<(), Box<dyn Error>> {
+    // integers prefer the longest candidate, leaving the rest to the string.
+    let source = "42 is the answer";
 
-    fn inner() -> Result<(), crate::ScanError> {
-        let source = "42 is the answer";
+    let answer: i32;
+    let descriptor: String;
+    crate::scanfmt!(source, "{}{}", answer, descriptor);
 
-        let _answer: i32;
-        let _descriptor: String;
-        crate::scanfmt!(source, "{}{}", _answer, _descriptor);
-        Ok(())
+    assert_eq!(42, answer);
+    assert_eq!(" is the answer", descriptor);
+
+    Ok(())
+}
+
+#[test]
+fn backtracking() -> Result<(), Box<dyn Error>> {
+    // the string has to extend past two occurrences of the literal
+    // for the integer to scan.
+    let source = "1-2-3-4";
+
+    let range: String;
+    let end: i32;
+    crate::scanfmt!(source, "{}-{}", range, end);
+    assert_eq!("1-2-3", range);
+    assert_eq!(4, end);
+
+    let source = "12ab34";
+
+    let a: u32;
+    let b: String;
+    let c: u32;
+    crate::scanfmt!(source, "{}{}{}", a, b, c);
+    assert_eq!((12, "ab", 34), (a, &*b, c));
+
+    Ok(())
+}
+
+#[test]
+fn backtracking_failure() {
+    fn inner(source: &str) -> Result<(String, u8), crate::ScanError> {
+        let a;
+        let b;
+        crate::scanfmt!(source, "{}{}", a, b);
+        Ok((a, b))
     }
 
-    match inner() {
-        Err(crate::ScanError::Custom(boxed)) => {
+    assert_eq!(
+        ("too many digits: 1".to_owned(), 234),
+        inner("too many digits: 1234").unwrap()
+    );
+
+    // the only split leaves nothing for the integer.
+    let e = inner("answer: x").unwrap_err();
+    assert_eq!(Some(9), e.offset());
+    assert_eq!(1, e.piece().unwrap().index());
+    match e.into_kind() {
+        crate::ScanErrorKind::Custom(boxed) => {
             let pie = boxed.downcast::<ParseIntError>().expect("should be PIE");
             assert_eq!(IntErrorKind::Empty, *pie.kind());
         }
         _ => panic!(),
     }
 }
+
+#[test]
+fn argument_order() -> Result<(), Box<dyn Error>> {
+    let source = "1/2";
+
+    let a: i32;
+    let b: i32;
+    crate::scanfmt!(source, "{1}/{0}", a, b);
+    assert_eq!((2, 1), (a, b));
+
+    let c: i32;
+    let d: i32;
+    crate::scanfmt!(source, "{d}/{c}", c, d);
+    assert_eq!((2, 1), (c, d));
+
+    Ok(())
+}
+
+#[test]
+fn shadowed_macros() -> Result<(), Box<dyn Error>> {
+    // the expansion does not use the macros in scope.
+    #[allow(unused_macros)]
+    macro_rules! unreachable {
+        ($($tt:tt)*) => {
+            compile_error!("the expansion used the `unreachable` in scope")
+        };
+    }
+
+    let a: i32;
+    crate::scanfmt!("1", "{}", a);
+    assert_eq!(1, a);
+
+    Ok(())
+}
+
+#[test]
+fn error_location() {
+    fn inner(source: &str) -> Result<(u16, u32), crate::ScanError> {
+        let a;
+        let b;
+        crate::scanfmt!(source, "a: {a}, b: {b}", a, b);
+        Ok((a, b))
+    }
+
+    let e = inner("a: 5, c: 6").unwrap_err();
+    assert!(matches!(e.kind(), crate::ScanErrorKind::LiteralNotFound));
+    assert_eq!(Some(3), e.offset());
+    assert_eq!(Some(", b: "), e.expected());
+    let piece = e.piece().unwrap();
+    assert_eq!(1, piece.index());
+    assert_eq!("{a}", piece.source());
+    assert_eq!(
+        r#"expected ", b: " at byte 3 while scanning `{a}`"#,
+        e.to_string()
+    );
+
+    let e = inner("b: 5, a: 6").unwrap_err();
+    assert!(matches!(e.kind(), crate::ScanErrorKind::LiteralMismatch));
+    assert_eq!(Some(0), e.offset());
+    assert_eq!(Some("a: "), e.expected());
+    assert_eq!(0, e.piece().unwrap().index());
+
+    let e = inner("a: 5, b: x").unwrap_err();
+    assert!(matches!(e.kind(), crate::ScanErrorKind::Custom(_)));
+    assert_eq!(Some(9), e.offset());
+    assert_eq!("{b}", e.piece().unwrap().source());
+}
+
+#[test]
+fn literal_first_char_in_value() -> Result<(), Box<dyn Error>> {
+    let source = "a: hello, world, b: 42";
+
+    let a: String;
+    let b: u8;
+    crate::scanfmt!(source, "a: {}, b: {}", a, b);
+
+    assert_eq!("hello, world", a);
+    assert_eq!(42, b);
+
+    // the first occurrence of the literal leaves nothing to scan as an
+    // integer, so the next one is tried.
+    let source = "-5-3";
+
+    let start: i32;
+    let end: i32;
+    crate::scanfmt!(source, "{}-{}", start, end);
+    assert_eq!(-5, start);
+    assert_eq!(3, end);
+
+    Ok(())
+}
+
+#[test]
+fn trailing_input() -> Result<(), Box<dyn Error>> {
+    fn inner(source: &str) -> Result<i32, crate::ScanError> {
+        let a;
+        crate::scanfmt!(source, "a: {}!", a);
+        Ok(a)
+    }
+
+    assert_eq!(5, inner("a: 5!")?);
+
+    let e = inner("a: 5!garbage").unwrap_err();
+    assert_eq!(Some(5), e.offset());
+    assert_eq!(
+        r#"unexpected trailing input "garbage" at byte 5"#,
+        e.to_string()
+    );
+    match e.into_kind() {
+        crate::ScanErrorKind::TrailingInput(rest) => assert_eq!("garbage", rest),
+        _ => panic!(),
+    }
+
+    // `..` allows input to remain after the pattern.
+    let a: i32;
+    crate::scanfmt!("a: 5!garbage", "a: {}!", a, ..);
+    assert_eq!(5, a);
+
+    let a: i32;
+    crate::scanfmt!("5 and more", "{}", a, ..);
+    assert_eq!(5, a);
+
+    Ok(())
+}
+
+#[test]
+fn prefix() -> Result<(), Box<dyn Error>> {
+    let source = "move 3 4;quit;";
+
+    let x: i32;
+    let y: i32;
+    let rest = crate::scanfmt_prefix!(source, "move {} {};", x, y);
+    assert_eq!((3, 4), (x, y));
+    assert_eq!("quit;", rest);
+
+    let command: String;
+    let rest = crate::scanfmt_prefix!(rest, "{};", command);
+    assert_eq!("quit", command);
+    assert_eq!("", rest);
+
+    // the last value is as long as it can be.
+    let n: u32;
+    let rest = crate::scanfmt_prefix!("123abc", "{}", n);
+    assert_eq!(123, n);
+    assert_eq!("abc", rest);
+
+    Ok(())
+}
+
+#[test]
+fn scan_expression() {
+    let r: Result<(i32, i32), _> = crate::scan!("42x100", "{}x{}");
+    assert_eq!((42, 100), r.unwrap());
+
+    let parse = |s: &str| crate::scan!(s, "{w}x{h}").ok();
+    let (w, h): (u32, u32) = parse("3x4").unwrap();
+    assert_eq!((3, 4), (w, h));
+    assert_eq!(None::<(u32, u32)>, parse("3y4"));
+
+    fn area(s: &str) -> Option<u64> {
+        let (w, h): (u64, u64) = crate::scan!(s, "{}x{}").ok()?;
+        Some(w * h)
+    }
+    assert_eq!(Some(12), area("3x4"));
+
+    let described = match "move 1" {
+        s if crate::scan!(s, "quit").is_ok() => "quit".to_owned(),
+        s => match crate::scan!(s, "move {}") {
+            Ok((n,)) => {
+                let n: i32 = n;
+                format!("move by {}", n)
+            }
+            Err(_) => "unknown".to_owned(),
+        },
+    };
+    assert_eq!("move by 1", described);
+
+    if let Ok((a, rest)) = crate::scan!("5 and more", "{} {}") {
+        let (a, rest): (u8, String) = (a, rest);
+        assert_eq!((5, "and more"), (a, &*rest));
+    } else {
+        panic!();
+    }
+
+    let prefix: Result<(i32,), _> = crate::scan!("5!garbage", "{}!", ..);
+    assert_eq!((5,), prefix.unwrap());
+}
+
+#[test]
+fn typed_placeholders() -> Result<(), Box<dyn Error>> {
+    crate::scanfmt!("8080 on 0xff", "{port: u16} on 0x{mask:x as u8}");
+    assert_eq!(8080, port);
+    assert_eq!(255, mask);
+
+    // types also constrain declared arguments.
+    let a;
+    let b;
+    crate::scanfmt!("-1/2", "{:i64}/{b:u8}", a, b);
+    assert_eq!(-1i64, a);
+    assert_eq!(2u8, b);
+
+    let (n, s) = crate::scan!("3 apples", "{:u32} {:String}")?;
+    assert_eq!((3, "apples"), (n, &*s));
+
+    Ok(())
+}
+
+#[test]
+fn place_arguments() -> Result<(), Box<dyn Error>> {
+    #[derive(Default)]
+    struct Range {
+        start: u32,
+        end: u32,
+    }
+
+    impl Range {
+        fn scan(&mut self, s: &str) -> Result<(), crate::ScanError> {
+            crate::scanfmt!(s, "{}..{}", self.start, self.end);
+            Ok(())
+        }
+    }
+
+    let mut range = Range::default();
+    range.scan("3..7")?;
+    assert_eq!((3, 7), (range.start, range.end));
+
+    fn scan_into(s: &str, out: &mut i8, slots: &mut [u8; 2]) -> Result<(), crate::ScanError> {
+        crate::scanfmt!(s, "{}: {hi}.{lo}", *out, lo = slots[1], hi = slots[0]);
+        Ok(())
+    }
+
+    let mut out = 0;
+    let mut slots = [0; 2];
+    scan_into("-4: 1.2", &mut out, &mut slots)?;
+    assert_eq!(-4, out);
+    assert_eq!([1, 2], slots);
+
+    Ok(())
+}
+
+#[test]
+fn implicit_capture() -> Result<(), Box<dyn Error>> {
+    let name: String;
+    let age: u8;
+    let mut total: u32;
+    crate::scanfmt!("Alice is 30, total 12", "{name} is {age}, total {total}");
+    assert_eq!(("Alice", 30, 12), (&*name, age, total));
+
+    // captures mix with arguments.
+    let mut pair = (0i32, 0i32);
+    crate::scanfmt!("1,2 +4", "{},{} +{total}", pair.0, pair.1);
+    assert_eq!(((1, 2), 4), (pair, total));
+
+    Ok(())
+}
+
+#[test]
+fn width() -> Result<(), Box<dyn Error>> {
+    let (year, month, day): (u16, u8, u8) = crate::scan!("20241018", "{:4}{:2}{:2}")?;
+    assert_eq!((2024, 10, 18), (year, month, day));
+
+    crate::scanfmt!("ff0a|ab", "{r:2x as u8}{g:2x as u8}|{rest:1 String}b");
+    assert_eq!((255, 10, "a"), (r, g, &*rest));
+
+    // widths count characters, not bytes.
+    let (name, n): (String, u32) = crate::scan!("héllo42", "{:5}{}")?;
+    assert_eq!(("héllo", 42), (&*name, n));
+
+    let e = crate::scan!("2024101", "{:4}{:2}{:2}")
+        .map(|(_, _, _): (u16, u8, u8)| ())
+        .unwrap_err();
+    assert!(matches!(e.kind(), crate::ScanErrorKind::Eof));
+    assert_eq!(Some(6), e.offset());
+
+    Ok(())
+}
+
+#[test]
+fn flexible_whitespace() -> Result<(), Box<dyn Error>> {
+    let (a,): (i32,) = crate::scan!("a:  5", "{!ws}a: {}")?;
+    assert_eq!(5, a);
+    let (a,): (i32,) = crate::scan!("a:5", "{!ws}a: {}")?;
+    assert_eq!(5, a);
+    let (a, b): (i32, String) = crate::scan!(" 1 \t two\n", "{!ws} {} {} ")?;
+    assert_eq!((1, "two"), (a, &*b));
+
+    // values before trailing whitespace in a prefix are not cut short.
+    let a;
+    let rest = crate::scanfmt_prefix!("123   rest", "{!ws}{} ", a);
+    assert_eq!((123, "rest"), (a, rest));
+
+    // `{!ws+}` requires at least one whitespace character.
+    let r: Result<(i32, i32), _> = crate::scan!("1 2", "{!ws+}{} {}");
+    assert_eq!((1, 2), r?);
+    let e = crate::scan!("1,2", "{!ws+}{}, {}")
+        .map(|(_, _): (i32, i32)| ())
+        .unwrap_err();
+    assert_eq!(Some(2), e.offset());
+    assert_eq!(Some(" "), e.expected());
+
+    // the directive only applies to the literals after it.
+    let r: Result<(i32, i32), _> = crate::scan!("1  2", "{} {!ws}{}");
+    assert!(r.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn char_class() -> Result<(), Box<dyn Error>> {
+    let (ident, rest): (String, String) = crate::scan!("foo_bar1+2", "{:[a-zA-Z_]}{}")?;
+    assert_eq!(("foo_bar", "1+2"), (&*ident, &*rest));
+
+    crate::scanfmt!(
+        "a,b}c,12",
+        "{first:[^,] as String},{second:[^,] as String},{n:[0-9] as u32}"
+    );
+    assert_eq!(("a", "b}c", 12), (&*first, &*second, n));
+
+    // `]` right after the bracket, escapes and a trailing `-` are literal.
+    let (s,): (String,) = crate::scan!("]-\\]x", "{:[]\\\\-]}x")?;
+    assert_eq!("]-\\]", s);
+
+    // widths limit the run.
+    let (a, b): (String, String) = crate::scan!("abcdef", "{:3[a-z]}{}")?;
+    assert_eq!(("abc", "def"), (&*a, &*b));
+
+    let e = crate::scan!("1abc", "{:[a-z]}{}")
+        .map(|(_, _): (String, String)| ())
+        .unwrap_err();
+    assert!(matches!(e.kind(), crate::ScanErrorKind::ClassMismatch));
+    assert_eq!(
+        "expected a character in [a-z] at byte 0 while scanning `{:[a-z]}`",
+        e.to_string()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn discard() -> Result<(), Box<dyn Error>> {
+    let name: String;
+    let score: u32;
+    crate::scanfmt!("7,alice,ignored,99", "{_:u8},{},{_},{}", name, score);
+    assert_eq!(("alice", 99), (&*name, score));
+
+    let (a, b): (i32, i32) = crate::scan!("1 ff 2", "{} {_:x} {}")?;
+    assert_eq!((1, 2), (a, b));
+
+    let e = crate::scan!("1 fg 2", "{} {_:x} {}")
+        .map(|(_, _): (i32, i32)| ())
+        .unwrap_err();
+    assert_eq!(Some(2), e.offset());
+    assert_eq!("{_:x}", e.piece().unwrap().source());
+
+    // discarded values accept the same digits as the integers.
+    let (a,): (u32,) = crate::scan!("ff", "{:X}")?;
+    assert_eq!(255, a);
+    let (b,): (i32,) = crate::scan!("ff 2", "{_:X} {}")?;
+    assert_eq!(2, b);
+
+    let e = crate::scan!("x,1", "{_:u8},{}")
+        .map(|(_,): (i32,)| ())
+        .unwrap_err();
+    assert_eq!(Some(0), e.offset());
+
+    Ok(())
+}
+
+#[test]
+fn optional() -> Result<(), Box<dyn Error>> {
+    fn person(s: &str) -> Result<(String, Option<u32>), crate::ScanError> {
+        crate::scanfmt!(s, "{name:String}[ ({age:u32})]");
+        Ok((name, age))
+    }
+    assert_eq!(("bob".to_owned(), Some(42)), person("bob (42)")?);
+    assert_eq!(("bob".to_owned(), None), person("bob")?);
+    // a group that fails to scan is skipped, and its values are cleared.
+    assert_eq!(("bob (x)".to_owned(), None), person("bob (x)")?);
+
+    let (a, b, c): (String, Option<u8>, Option<u8>) = crate::scan!("x (1)", "{}[ ({}[/{}])]")?;
+    assert_eq!(("x".to_owned(), Some(1), None), (a, b, c));
+    let (a, b, c): (String, Option<u8>, Option<u8>) = crate::scan!("x (1/2)", "{}[ ({}[/{}])]")?;
+    assert_eq!(("x".to_owned(), Some(1), Some(2)), (a, b, c));
+
+    let (level, line): (String, Option<u32>) = crate::scan!("[warn] 7", "[[{}]][ {}]")?;
+    assert_eq!(("warn".to_owned(), Some(7)), (level, line));
+
+    let e = crate::scan!("1.x", "{}[.{}]")
+        .map(|(_, _): (u8, Option<u8>)| ())
+        .unwrap_err();
+    assert_eq!(Some(2), e.offset());
+
+    Ok(())
+}
+
+#[test]
+fn repetition() -> Result<(), Box<dyn Error>> {
+    let (nums,): (Vec<u32>,) = crate::scan!("1, 22, 333", r#"{:*", "}"#)?;
+    assert_eq!(vec![1, 22, 333], nums);
+
+    let (nums,): (Vec<u32>,) = crate::scan!("[]", r#"[[{:*","}]]"#)?;
+    assert!(nums.is_empty());
+
+    // separators have the escapes of string literals.
+    let (nums,): (Vec<u32>,) = crate::scan!("1,2", r#"{:*"\u{2c}"}"#)?;
+    assert_eq!(vec![1, 2], nums);
+    let (nums,): (Vec<u32>,) = crate::scan!("1\t2", r#"{:*"\x09"}"#)?;
+    assert_eq!(vec![1, 2], nums);
+    let pattern = crate::Pattern::<(Vec<u32>,)>::new(r#"{:*"\u{2c}"}"#)?;
+    assert_eq!((vec![1, 2],), pattern.scan("1,2")?);
+
+    fn words(s: &str) -> Result<(String, Vec<String>), crate::ScanError> {
+        crate::scanfmt!(s, r#"{cmd:String}: {args:*" " [a-z] as String}"#);
+        Ok((cmd, args))
+    }
+    let (cmd, args) = words("run: a bc d")?;
+    assert_eq!(
+        ("run", vec!["a", "bc", "d"]),
+        (&*cmd, args.iter().map(|s| &**s).collect())
+    );
+
+    // values are scanned again when a later piece fails.
+    let (hex, rest): (Vec<u8>, String) = crate::scan!("a;ff;zz", r#"{:*";" x};{}"#)?;
+    assert_eq!((vec![0xa, 0xff], "zz".to_owned()), (hex, rest));
+
+    // values at the end of a prefix are as long as they can be.
+    let nums: Vec<u32>;
+    let rest = crate::scanfmt_prefix!("12,34 rest", r#"{:*","}"#, nums);
+    assert_eq!((vec![12, 34], " rest"), (nums, rest));
+
+    let e = crate::scan!("1,2,x", r#"{:*","}"#)
+        .map(|(_,): (Vec<u8>,)| ())
+        .unwrap_err();
+    assert_eq!(Some(4), e.offset());
+
+    Ok(())
+}
+
+#[test]
+fn derive_struct() -> Result<(), Box<dyn Error>> {
+    use crate::Scan;
+
+    #[derive(Debug, PartialEq, crate::Scan)]
+    #[scan("({x}, {y})")]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    #[derive(Debug, PartialEq, crate::Scan)]
+    #[scan("{1}-{0}[ {2}]")]
+    struct Span(Point, Point, Option<String>);
+
+    #[derive(Debug, PartialEq, crate::Scan)]
+    #[scan("<{a}>")]
+    struct Wrapper<T> {
+        a: T,
+    }
+
+    assert_eq!(Wrapper { a: 5u8 }, Wrapper::scan("<5>")?);
+    assert_eq!(
+        Wrapper {
+            a: Point { x: 1, y: 2 }
+        },
+        Wrapper::scan("<(1, 2)>")?
+    );
+
+    assert_eq!(Point { x: 1, y: -2 }, Point::scan("(1, -2)")?);
+    assert!(Point::is_valid_start('('));
+    assert!(!Point::is_valid_start('1'));
+
+    let (a, b): (Point, Point) = crate::scan!("(1, 2)(3, 4)", "{}{}")?;
+    assert_eq!((Point { x: 1, y: 2 }, Point { x: 3, y: 4 }), (a, b));
+
+    let span = Span::scan("(3, 4)-(1, 2) name")?;
+    assert_eq!(Point { x: 1, y: 2 }, span.0);
+    assert_eq!(Point { x: 3, y: 4 }, span.1);
+    assert_eq!(Some("name"), span.2.as_deref());
+
+    let e = Point::scan("(1, x)").unwrap_err();
+    assert_eq!(Some(4), e.offset());
+    assert_eq!("{y}", e.piece().unwrap().source());
+
+    // errors in nested values are located in the whole input.
+    let e = crate::scan!("(1, 2)-(3, x)", "{}-{}")
+        .map(|(_, _): (Point, Point)| ())
+        .unwrap_err();
+    assert_eq!(Some(11), e.offset());
+
+    Ok(())
+}
+
+#[test]
+fn derive_enum() -> Result<(), Box<dyn Error>> {
+    use crate::Scan;
+
+    #[derive(Debug, PartialEq, crate::Scan)]
+    enum Command {
+        #[scan("move {x} {y}")]
+        Move {
+            x: i32,
+            y: i32,
+        },
+        #[scan("say {}")]
+        Say(String),
+        Quit,
+    }
+
+    assert_eq!(Command::Move { x: 1, y: -2 }, Command::scan("move 1 -2")?);
+    assert_eq!(Command::Say("hi".to_owned()), Command::scan("say hi")?);
+    assert_eq!(Command::Quit, Command::scan("Quit")?);
+    assert!(Command::is_valid_start('m'));
+    assert!(Command::is_valid_start('Q'));
+    assert!(!Command::is_valid_start('x'));
+
+    let (a, b): (Command, Command) = crate::scan!("Quit;say bye", "{};{}")?;
+    assert_eq!((Command::Quit, Command::Say("bye".to_owned())), (a, b));
+
+    // the variant that got the furthest reports the error.
+    let e = Command::scan("move 1 x").unwrap_err();
+    assert_eq!(Some(7), e.offset());
+    assert_eq!("{y}", e.piece().unwrap().source());
+
+    Ok(())
+}
+
+#[test]
+fn borrowed() -> Result<(), Box<dyn Error>> {
+    use std::borrow::Cow;
+
+    use crate::ScanBorrowed;
+
+    let input = String::from("key=5 a,b,c");
+    let (key, value, list): (&str, u32, Vec<&str>) = crate::scan!(&input, "{}={} {:*\",\"}")?;
+    assert_eq!(("key", 5), (key, value));
+    assert_eq!(vec!["a", "b", "c"], list);
+    // the values point into the input.
+    assert_eq!(input.as_ptr(), key.as_ptr());
+
+    let (name, nick): (Cow<str>, Option<&str>) = crate::scan!("ann (annie)", "{}[ ({})]")?;
+    assert!(matches!(name, Cow::Borrowed("ann")));
+    assert_eq!(Some("annie"), nick);
+
+    crate::scanfmt!("GET /index.html", "{method:&str} {path:[^ ] as &str}");
+    assert_eq!(("GET", "/index.html"), (method, path));
+
+    #[derive(Debug, PartialEq, crate::Scan)]
+    #[scan("{name}: {value}")]
+    struct Header<'a> {
+        name: &'a str,
+        value: Cow<'a, str>,
+    }
+
+    #[derive(Debug, PartialEq, crate::Scan)]
+    enum Line<'a> {
+        #[scan("> {0}")]
+        Quote(Header<'a>),
+        Empty,
+    }
+
+    let header = Header::scan("Host: example.com")?;
+    assert_eq!("Host", header.name);
+    assert_eq!("example.com", header.value);
+    assert!(Header::is_valid_start('H'));
+
+    let (line,): (Line,) = crate::scan!("> Accept: */*", "{}")?;
+    assert_eq!(
+        Line::Quote(Header {
+            name: "Accept",
+            value: "*/*".into()
+        }),
+        line
+    );
+    assert_eq!(Line::Empty, Line::scan("Empty")?);
+
+    #[derive(Debug, PartialEq, crate::Scan)]
+    #[scan("{name}={value}")]
+    struct Field<'a, T> {
+        name: &'a str,
+        value: T,
+    }
+
+    let field: Field<&str> = Field::scan("key=value")?;
+    assert_eq!(("key", "value"), (field.name, field.value));
+    let field: Field<u8> = Field::scan("key=5")?;
+    assert_eq!(5, field.value);
+
+    Ok(())
+}
+
+#[test]
+fn pattern() -> Result<(), Box<dyn Error>> {
+    use crate::Pattern;
+
+    let pattern = Pattern::<(u32, String)>::new("{}: {}")?;
+    assert_eq!((1, "a b".to_owned()), pattern.scan("1: a b")?);
+    assert_eq!((22, "c".to_owned()), pattern.scan("22: c")?);
+
+    let pattern = Pattern::<(String, Option<u8>, Vec<i32>)>::new(
+        r#"{name:[a-z]}[ ({age})]: {_} {nums:*","}"#,
+    )?;
+    assert_eq!(
+        ("bob".to_owned(), None, vec![1, -2]),
+        pattern.scan("bob: x 1,-2")?
+    );
+    assert_eq!(
+        ("bob".to_owned(), Some(3), vec![]),
+        pattern.scan("bob (3): x ")?
+    );
+
+    let pattern = Pattern::<(u32, Vec<u8>, i8)>::new(r#"{:x}-{:*"," X} {_:b} {:o}"#)?;
+    assert_eq!((0xff, vec![0xA, 0xB], -7), pattern.scan("ff-A,B 101 -7")?);
+    assert!(pattern.scan("ff-A,B 2 -7").is_err());
+
+    let e = Pattern::<(u32, u32)>::new("{}-{}")?
+        .scan("1-x")
+        .unwrap_err();
+    assert_eq!(Some(2), e.offset());
+    assert_eq!("{}", e.piece().unwrap().source());
+
+    let e = Pattern::<(u32,)>::new("{}-{}").unwrap_err();
+    assert_eq!(
+        "the pattern has 2 values, but the tuple has 1 elements",
+        e.to_string()
+    );
+    assert!(Pattern::<(u32,)>::new("[{}]").is_err());
+    let e = Pattern::<(String,)>::new("{:x}").unwrap_err();
+    assert_eq!(
+        "`{:x}` has a radix, but element 0 of the tuple cannot be scanned in it",
+        e.to_string()
+    );
+    assert!(Pattern::<(u32,)>::new("{").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn dynamic() -> Result<(), Box<dyn Error>> {
+    use crate::{DynPattern, Value};
+
+    let pattern = DynPattern::new("{host:str}:{port:u16} {ratio:f64}")?;
+    let values = pattern.scan_map("example.com:443 0.5")?;
+    assert_eq!(3, values.len());
+    assert_eq!(Value::Str("example.com".to_owned()), values["host"]);
+    assert_eq!(Value::U16(443), values["port"]);
+    assert_eq!(Value::F64(0.5), values["ratio"]);
+
+    let pattern = DynPattern::new(r#"{:x as u8} {_:i32}[ ({age:u8})] {nums:*","i64} {}"#)?;
+    assert_eq!(
+        vec![None, Some("age"), Some("nums"), None],
+        pattern.names().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![
+            Value::U8(0xff),
+            Value::Option(None),
+            Value::Vec(vec![Value::I64(1), Value::I64(-2)]),
+            Value::Str("rest".to_owned()),
+        ],
+        pattern.scan("ff -3 1,-2 rest")?
+    );
+    assert_eq!(
+        vec![
+            Value::U8(1),
+            Value::Option(Some(Box::new(Value::U8(30)))),
+            Value::Vec(vec![]),
+            Value::Str("x".to_owned()),
+        ],
+        pattern.scan("1 0 (30)  x")?
+    );
+    assert!(pattern.scan("1 x 2 y").is_err());
+
+    let e = DynPattern::new("{port:u17}").unwrap_err();
+    assert_eq!("`{port:u17}` has an unknown type `u17`", e.to_string());
+    assert!(DynPattern::new("{x:u8} {x:u8}").is_err());
+    assert!(DynPattern::new("{x:x as f32}").is_err());
+    assert!(DynPattern::new("{x:x}").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn read_line() -> Result<(), Box<dyn Error>> {
+    use crate::{scan_read, ScanError, ScanErrorKind};
+    use std::io::Cursor;
+
+    let mut reader = Cursor::new("3 4\r\nname: bob\n\n");
+    let (a, b): (u32, u32);
+    scan_read!(reader, "{} {}", a, b);
+    assert_eq!((3, 4), (a, b));
+    scan_read!(&mut reader, "name: {name:String}");
+    assert_eq!("bob", name);
+    scan_read!(reader, "");
+
+    let e = (|| -> Result<(), ScanError> {
+        scan_read!(reader, "");
+        Ok(())
+    })()
+    .unwrap_err();
+    assert!(matches!(e.kind(), ScanErrorKind::Eof));
+
+    let e = (|| -> Result<(), ScanError> {
+        scan_read!(Cursor::new(b"\xff\n"), "{_}");
+        Ok(())
+    })()
+    .unwrap_err();
+    assert!(matches!(e.kind(), ScanErrorKind::Io(_)));
+    assert!(e.source().is_some());
+
+    Ok(())
+}
+
+#[test]
+fn lines() -> Result<(), Box<dyn Error>> {
+    use crate::{scan_lines, Pattern};
+    use std::io::Cursor;
+
+    let input = "a=1\nb=2\r\nc=x\nd=4";
+    let mut lines = scan_lines!(Cursor::new(input), "{:[a-z]}={:u32}");
+    let (key, value): (String, u32) = lines.next().unwrap()?;
+    assert_eq!(("a".to_owned(), 1), (key, value));
+    assert_eq!(("b".to_owned(), 2), lines.next().unwrap()?);
+    let e = lines.next().unwrap().unwrap_err();
+    assert_eq!(Some(3), e.line());
+    assert_eq!(Some(2), e.offset());
+    assert_eq!(
+        "invalid digit found in string on line 3 at byte 2 while scanning `{:u32}`",
+        e.to_string()
+    );
+    assert_eq!(("d".to_owned(), 4), lines.next().unwrap()?);
+    assert!(lines.next().is_none());
+    assert_eq!(4, lines.line());
+
+    let pattern = Pattern::<(u32, u32)>::new("{} {}")?;
+    let pairs = pattern
+        .lines(Cursor::new("1 2\n3 4\n"))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(vec![(1, 2), (3, 4)], pairs);
+
+    let e = pattern
+        .lines(Cursor::new(b"1 2\n\xff\n"))
+        .find_map(Result::err)
+        .unwrap();
+    assert!(matches!(e.kind(), crate::ScanErrorKind::Io(_)));
+    assert_eq!(Some(2), e.line());
+
+    Ok(())
+}
+
+#[test]
+fn stream() -> Result<(), Box<dyn Error>> {
+    use crate::{scan, Pattern, Scanner};
+    use std::io::Read;
+
+    // yields one byte per read, so that records span many reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let input = "1: α\r\n\r\n2: βγ\r\n\r\n3: \r\n";
+    let mut scanner = Scanner::new(Trickle(input.as_bytes()), "\r\n\r\n");
+    let mut records = Vec::new();
+    while let Some(record) = scanner.next_record() {
+        let (id, text): (u32, String) = scan!(record?, "{}: {}")?;
+        records.push((id, text));
+    }
+    assert_eq!(
+        vec![
+            (1, "α".to_owned()),
+            (2, "βγ".to_owned()),
+            (3, "\r\n".to_owned())
+        ],
+        records
+    );
+    assert_eq!(3, scanner.records());
+
+    let long = "9".repeat(20000);
+    let input = format!("{}|1|", long);
+    let pattern = Pattern::<(String,)>::new("{:[0-9]}")?;
+    let mut scanner = Scanner::new(input.as_bytes(), "|");
+    assert_eq!(long, scanner.scan(&pattern).unwrap()?.0);
+    assert_eq!("1", scanner.scan(&pattern).unwrap()?.0);
+    assert!(scanner.scan(&pattern).is_none());
+
+    // the consumed input is dropped while the long record is read.
+    let input = format!("1|{}|1|{}|", long, long);
+    let mut scanner = Scanner::new(Trickle(input.as_bytes()), "|");
+    for expected in ["1", &long, "1", &long] {
+        assert_eq!(expected, scanner.scan(&pattern).unwrap()?.0);
+    }
+    assert!(scanner.scan(&pattern).is_none());
+
+    let mut scanner = Scanner::new(&b"ok;\xff;"[..], ";");
+    assert_eq!("ok", scanner.next_record().unwrap()?);
+    let e = scanner.next_record().unwrap().unwrap_err();
+    assert!(matches!(e.kind(), crate::ScanErrorKind::Io(_)));
+    assert!(scanner.next_record().is_none());
+
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn tokio_lines() -> Result<(), Box<dyn Error>> {
+    use crate::{scan, Pattern};
+    use tokio::io::{AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await?;
+        for chunk in ["SET a 1\r\nSE", "T b 2\r\n", "GET a\r\n", "DEL\r\n"] {
+            stream.write_all(chunk.as_bytes()).await?;
+            stream.flush().await?;
+        }
+        std::io::Result::Ok(())
+    });
+
+    let stream = BufReader::new(TcpStream::connect(addr).await?);
+    let mut lines = crate::tokio::lines(stream, |line| scan!(line, "{} {}[ {}]"));
+    let (command, key, value): (String, String, Option<u32>) = lines.next_line().await.unwrap()?;
+    assert_eq!(("SET", "a", Some(1)), (&*command, &*key, value));
+    let (command, key, value) = lines.next_line().await.unwrap()?;
+    assert_eq!(("SET", "b", Some(2)), (&*command, &*key, value));
+    let (command, key, value) = lines.next_line().await.unwrap()?;
+    assert_eq!(("GET", "a", None), (&*command, &*key, value));
+    let e = lines.next_line().await.unwrap().unwrap_err();
+    assert_eq!(Some(4), e.line());
+    assert!(lines.next_line().await.is_none());
+    server.await??;
+
+    let pattern = Pattern::<(u32, u32)>::new("{}-{}")?;
+    let mut scanner = crate::tokio::Scanner::new(&b"1-2;3-4"[..], ";");
+    assert_eq!((1, 2), scanner.scan(&pattern).await.unwrap()?);
+    assert_eq!((3, 4), scanner.scan(&pattern).await.unwrap()?);
+    assert!(scanner.next_record().await.is_none());
+
+    Ok(())
+}
+
+#[cfg(feature = "futures-io")]
+#[test]
+fn futures_lines() -> Result<(), Box<dyn Error>> {
+    use crate::scan;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    runtime.block_on(async {
+        let mut lines = crate::futures::lines(&b"1 2\r\n3 4\n"[..], |line| scan!(line, "{} {}"));
+        let (a, b): (u8, u8) = lines.next_line().await.unwrap()?;
+        assert_eq!((1, 2), (a, b));
+        assert_eq!((3, 4), lines.next_line().await.unwrap()?);
+        assert!(lines.next_line().await.is_none());
+        assert_eq!(2, lines.line());
+        Ok(())
+    })
+}
@@ -0,0 +1,6 @@
+//! Async scanning of [`tokio::io::AsyncBufRead`]s, which is enabled by the
+//! `tokio` feature.
+//!
+//! [`tokio::io::AsyncBufRead`]: ::tokio::io::AsyncBufRead
+
+crate::async_io::async_io!(::tokio::io::AsyncBufRead);